@@ -1,12 +1,6 @@
-use bevy::{prelude::{Entity, Resource}, utils::{HashMap, HashSet}};
+use bevy::{prelude::{Entity, Resource}, utils::HashMap};
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct CollisionState {
    pub colliding_entities: HashMap<Entity, f32>,
 }
-
-impl Default for CollisionState {
-    fn default() -> Self {
-        Self { colliding_entities: Default::default() }
-    }
-}
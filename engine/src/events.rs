@@ -1,7 +1,59 @@
-use bevy::prelude::{Entity, Event};
+use bevy::{math::Vec2, prelude::{Entity, Event}};
+use serde::{Deserialize, Serialize};
 
-#[derive(Event)]
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Poison,
+    Frost,
+}
+
+/// A single hit. Every source of damage sends one of these instead of
+/// touching `Health` directly, see `DamageSet` for how they are processed.
+#[derive(Event, Clone)]
 pub struct DamageEvent {
     pub source: Entity,
     pub target: Entity,
+    pub amount: f32,
+    pub damage_type: DamageType,
+    /// Where the hit came from, e.g. to push the target away from it
+    pub source_position: Vec2,
+}
+
+/// Sent once a hit went through the damage pipeline, with the health it
+/// actually took from the target.
+#[derive(Event)]
+pub struct DamageDealtEvent {
+    pub source: Entity,
+    pub target: Entity,
+    pub amount: f32,
+    pub damage_type: DamageType,
+    pub source_position: Vec2,
+}
+
+/// Asks for `amount` health to be restored to `target`.
+#[derive(Event)]
+pub struct HealEvent {
+    pub source: Entity,
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// Sent once a heal was applied, with the health actually restored.
+#[derive(Event)]
+pub struct HealedEvent {
+    pub source: Entity,
+    pub target: Entity,
+    pub amount: f32,
+}
+
+/// Sent when damage brings an entity's health to zero, before it is despawned.
+#[derive(Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Entity,
+    pub position: Vec2,
 }
@@ -0,0 +1,114 @@
+use bevy::math::{IVec2, Vec2};
+
+/// Size of each tile in pixels
+pub const TILE_SIZE: f32 = 32.0;
+
+// World positions are in pixels with y pointing up. Tile (0, 0) is centred on
+// the world origin and chunk (0, 0) starts at that tile, so each tile covers
+// half a tile to either side of its centre.
+
+/// World position of the centre of a tile.
+pub fn tile_to_world(tile: IVec2) -> Vec2 {
+    tile.as_vec2() * TILE_SIZE
+}
+
+/// Tile containing a world position.
+pub fn world_to_tile(position: Vec2) -> IVec2 {
+    ((position + TILE_SIZE / 2.0) / TILE_SIZE).floor().as_ivec2()
+}
+
+/// Centre of the tile containing a world position.
+pub fn snap_to_tile(position: Vec2) -> Vec2 {
+    tile_to_world(world_to_tile(position))
+}
+
+/// Chunk containing a tile.
+pub fn tile_to_chunk(tile: IVec2, chunk_size: usize) -> IVec2 {
+    tile.div_euclid(IVec2::splat(chunk_size as i32))
+}
+
+/// Position of a tile within its chunk.
+pub fn tile_in_chunk(tile: IVec2, chunk_size: usize) -> IVec2 {
+    tile.rem_euclid(IVec2::splat(chunk_size as i32))
+}
+
+/// First tile of a chunk, the one at its bottom left.
+pub fn chunk_to_tile(chunk: IVec2, chunk_size: usize) -> IVec2 {
+    chunk * chunk_size as i32
+}
+
+/// Chunk containing a world position.
+pub fn world_to_chunk(position: Vec2, chunk_size: usize) -> IVec2 {
+    tile_to_chunk(world_to_tile(position), chunk_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNK_SIZE: usize = 16;
+
+    #[test]
+    fn tiles_cover_half_a_tile_around_their_centre() {
+        assert_eq!(world_to_tile(Vec2::ZERO), IVec2::ZERO);
+        assert_eq!(world_to_tile(Vec2::splat(15.9)), IVec2::ZERO);
+        assert_eq!(world_to_tile(Vec2::splat(-16.0)), IVec2::ZERO);
+        assert_eq!(world_to_tile(Vec2::splat(16.0)), IVec2::ONE);
+        assert_eq!(world_to_tile(Vec2::splat(-16.1)), IVec2::NEG_ONE);
+        assert_eq!(world_to_tile(Vec2::new(-48.1, 47.9)), IVec2::new(-2, 1));
+    }
+
+    #[test]
+    fn tile_centres_map_back_to_their_tile() {
+        for tile in [IVec2::ZERO, IVec2::new(-1, 5), IVec2::new(-17, -33)] {
+            assert_eq!(world_to_tile(tile_to_world(tile)), tile);
+        }
+    }
+
+    #[test]
+    fn negative_tiles_belong_to_negative_chunks() {
+        let size = CHUNK_SIZE as i32;
+
+        assert_eq!(tile_to_chunk(IVec2::splat(-1), CHUNK_SIZE), IVec2::splat(-1));
+        assert_eq!(tile_in_chunk(IVec2::splat(-1), CHUNK_SIZE), IVec2::splat(size - 1));
+
+        assert_eq!(tile_to_chunk(IVec2::splat(-size), CHUNK_SIZE), IVec2::splat(-1));
+        assert_eq!(tile_in_chunk(IVec2::splat(-size), CHUNK_SIZE), IVec2::ZERO);
+
+        assert_eq!(tile_to_chunk(IVec2::splat(-size - 1), CHUNK_SIZE), IVec2::splat(-2));
+        assert_eq!(tile_in_chunk(IVec2::splat(-size - 1), CHUNK_SIZE), IVec2::splat(size - 1));
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let size = CHUNK_SIZE as i32;
+
+        assert_eq!(tile_to_chunk(IVec2::ZERO, CHUNK_SIZE), IVec2::ZERO);
+        assert_eq!(tile_to_chunk(IVec2::splat(size - 1), CHUNK_SIZE), IVec2::ZERO);
+        assert_eq!(tile_in_chunk(IVec2::splat(size - 1), CHUNK_SIZE), IVec2::splat(size - 1));
+        assert_eq!(tile_to_chunk(IVec2::splat(size), CHUNK_SIZE), IVec2::ONE);
+        assert_eq!(tile_in_chunk(IVec2::splat(size), CHUNK_SIZE), IVec2::ZERO);
+        assert_eq!(chunk_to_tile(IVec2::new(-1, 2), CHUNK_SIZE), IVec2::new(-size, 2 * size));
+    }
+
+    #[test]
+    fn tiles_round_trip_through_their_chunk() {
+        let size = CHUNK_SIZE as i32;
+        for y in -2 * size - 1..=2 * size + 1 {
+            for x in -2 * size - 1..=2 * size + 1 {
+                let tile = IVec2::new(x, y);
+                let chunk = tile_to_chunk(tile, CHUNK_SIZE);
+                let local = tile_in_chunk(tile, CHUNK_SIZE);
+
+                assert!(local.cmpge(IVec2::ZERO).all() && local.cmplt(IVec2::splat(size)).all());
+                assert_eq!(chunk_to_tile(chunk, CHUNK_SIZE) + local, tile);
+            }
+        }
+    }
+
+    #[test]
+    fn world_positions_left_of_the_origin_are_in_chunk_minus_one() {
+        assert_eq!(world_to_chunk(Vec2::new(-16.1, 0.0), CHUNK_SIZE), IVec2::new(-1, 0));
+        assert_eq!(world_to_chunk(Vec2::new(-16.0, 0.0), CHUNK_SIZE), IVec2::ZERO);
+    }
+}
@@ -11,11 +11,52 @@ impl Health {
         Self { current: max, max }
     }
 
+    /// Changes the maximum health, keeping the same fraction of it left.
+    pub fn set_max(&mut self, max: f32) {
+        if self.max > 0.0 {
+            self.current *= max / self.max;
+        }
+        self.max = max;
+    }
+
     pub fn apply_damage(&mut self, damage: f32) {
         self.current = (self.current - damage).max(0.0);
     }
 
+    /// Restores up to `amount` health and returns how much was actually restored.
+    pub fn heal(&mut self, amount: f32) -> f32 {
+        let healed = amount.min(self.max - self.current).max(0.0);
+        self.current += healed;
+        healed
+    }
+
     pub fn is_dead(&self) -> bool {
         self.current == 0.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_max_keeps_the_fraction_left() {
+        let mut health = Health::new(40.0);
+        health.apply_damage(10.0);
+
+        health.set_max(80.0);
+
+        assert_eq!(health.max, 80.0);
+        assert_eq!(health.current, 60.0);
+    }
+
+    #[test]
+    fn set_max_leaves_the_dead_dead() {
+        let mut health = Health::new(40.0);
+        health.apply_damage(40.0);
+
+        health.set_max(80.0);
+
+        assert!(health.is_dead());
+    }
+}
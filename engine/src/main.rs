@@ -1,32 +1,59 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
-use events::DamageEvent;
+use events::{DamageDealtEvent, DamageEvent, DeathEvent, HealEvent, HealedEvent};
 use plugins::{
-    collision::CollisionPlugin, damage::DamagePlugin, enemy::EnemyPlugin, game_over::GameOverPlugin, map::MapPlugin, menu::MenuPlugin, player::PlayerPlugin
+    collision::CollisionPlugin, combat_log::CombatLogPlugin, damage::DamagePlugin, enemy::EnemyPlugin, game_over::GameOverPlugin, hit_reaction::HitReactionPlugin, map::{MapGenConfig, MapPlugin}, menu::MenuPlugin, pathfinding::PathfindingPlugin, pause::PausePlugin, player::PlayerPlugin, progression::ProgressionPlugin, save::SavePlugin, spawner::SpawnerPlugin, stats::StatsPlugin, steering::SteeringPlugin, ui::UiPlugin, weapon::WeaponPlugin
 };
-use state::{AppState, GameState};
+use state::StatePlugin;
 
 mod events;
+mod grid;
 mod plugins;
 mod state;
 mod health;
 mod collision_state;
 
 fn main() {
+    let (map_gen_config, args_error) = match MapGenConfig::from_args(std::env::args().skip(1)) {
+        Ok(config) => (config, None),
+        Err(err) => (MapGenConfig::default(), Some(err)),
+    };
+
     App::new()
+        .insert_resource(map_gen_config)
         .add_event::<DamageEvent>() 
+        .add_event::<DamageDealtEvent>()
+        .add_event::<HealEvent>()
+        .add_event::<HealedEvent>()
+        .add_event::<DeathEvent>()
         .add_plugins(DefaultPlugins)
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+        .add_plugins(UiPlugin)
         .add_plugins(MenuPlugin)
         .add_plugins(MapPlugin)
         .add_plugins(PlayerPlugin)
         .add_plugins(EnemyPlugin)
+        .add_plugins(PathfindingPlugin)
+        .add_plugins(SteeringPlugin)
+        .add_plugins(WeaponPlugin)
+        .add_plugins(SpawnerPlugin)
         .add_plugins(CollisionPlugin)
         .add_plugins(DamagePlugin)
+        .add_plugins(HitReactionPlugin)
+        .add_plugins(ProgressionPlugin)
+        .add_plugins(CombatLogPlugin)
+        .add_plugins(StatsPlugin)
         .add_plugins(GameOverPlugin)
+        .add_plugins(PausePlugin)
+        .add_plugins(SavePlugin)
+        .add_plugins(StatePlugin)
         .add_systems(Startup, setup)
-        .init_state::<AppState>()
-        .init_state::<GameState>()
+        // Logged once the log plugin is set up, it isn't when the args are parsed
+        .add_systems(Startup, move || {
+            if let Some(err) = &args_error {
+                error!("{err}, using the default world");
+            }
+        })
         .run();
 }
 
@@ -1,7 +1,7 @@
 use bevy::{prelude::*};
 use bevy_rapier2d::prelude::*;
 
-use crate::{collision_state::CollisionState, events::DamageEvent, state::AppState};
+use crate::{collision_state::CollisionState, state::AppState};
 
 use super::{enemy::Enemy, player::Player};
 
@@ -11,10 +11,15 @@ impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
         app
         .insert_resource(CollisionState::default())
-        .add_systems(Update, handle_collisions.run_if(in_state(AppState::InGame)));
+        .add_systems(Update, handle_collisions.run_if(in_state(AppState::InGame)))
+        .add_systems(OnExit(AppState::InGame), reset_collisions);
     }
 }
 
+/// Contacts of the finished run would otherwise hurt the next player.
+fn reset_collisions(mut collision_state: ResMut<CollisionState>) {
+    collision_state.colliding_entities.clear();
+}
 
 fn handle_collisions(
     mut collision_events: EventReader<CollisionEvent>,
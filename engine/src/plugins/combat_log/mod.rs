@@ -0,0 +1,286 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    input::ButtonInput,
+    log::{error, info},
+    prelude::{
+        in_state, BuildChildren, ChildBuild, Commands, Component, DetectChanges, Entity, Event,
+        EventReader, EventWriter, IntoSystemConfigs, KeyCode, Name, OnEnter, OnExit, Query, Res, ResMut,
+        Resource, StateScoped, Text, Visibility, With,
+    },
+    text::{TextColor, TextFont},
+    time::Time,
+    ui::{BackgroundColor, Node, PositionType, UiRect, Val},
+    utils::default,
+};
+use serde::Serialize;
+
+use crate::{
+    events::{DamageDealtEvent, DamageType, DeathEvent, HealedEvent},
+    state::{AppState, GameState},
+};
+
+use super::damage::DamageSet;
+
+pub struct CombatLogPlugin;
+
+impl Plugin for CombatLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatLog>()
+            .add_event::<CombatLogEntry>()
+            .add_systems(OnEnter(AppState::InGame), (reset_combat_log, spawn_overlay))
+            .add_systems(
+                Update,
+                (
+                    // Entities killed this frame are despawned at the end of
+                    // `DamageSet::Death`, so their names are still readable here
+                    record_combat_events.in_set(DamageSet::Death),
+                    store_entries.after(record_combat_events),
+                    (toggle_overlay, update_overlay),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            // Runs left from the pause overlay never reach game over
+            .add_systems(OnEnter(GameState::GameOver), export_combat_log)
+            .add_systems(OnExit(AppState::InGame), export_combat_log);
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CombatLogKind {
+    Hit,
+    Kill,
+    Heal,
+}
+
+/// One line of the combat log, also sent as an event when it is recorded.
+#[derive(Event, Serialize, Clone, Debug)]
+pub struct CombatLogEntry {
+    /// Seconds since the run started
+    pub time: f32,
+    pub kind: CombatLogKind,
+    #[serde(serialize_with = "serialize_entity")]
+    pub source: Entity,
+    pub source_name: String,
+    #[serde(serialize_with = "serialize_entity")]
+    pub target: Entity,
+    pub target_name: String,
+    pub amount: f32,
+    pub damage_type: Option<DamageType>,
+    /// Where the hit came from, only set for hits
+    pub source_position: Option<[f32; 2]>,
+}
+
+fn serialize_entity<S: serde::Serializer>(
+    entity: &Entity,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(entity.to_bits())
+}
+
+/// Every hit, kill and heal of the current run.
+#[derive(Resource, Default)]
+pub struct CombatLog {
+    pub entries: Vec<CombatLogEntry>,
+    started_at: f32,
+    /// Set once the run's log has been written, so leaving the run after game
+    /// over doesn't write it again
+    exported: bool,
+}
+
+/// Directory the combat log of each run is exported to
+const EXPORT_DIRECTORY: &str = "combat_logs";
+
+/// How many of the latest entries the overlay shows
+const OVERLAY_LINES: usize = 12;
+
+#[derive(Component)]
+struct CombatLogOverlay;
+
+#[derive(Component)]
+struct CombatLogText;
+
+fn reset_combat_log(mut combat_log: ResMut<CombatLog>, time: Res<Time>) {
+    *combat_log = CombatLog {
+        entries: Vec::new(),
+        started_at: time.elapsed_secs(),
+        exported: false,
+    };
+}
+
+fn record_combat_events(
+    time: Res<Time>,
+    combat_log: Res<CombatLog>,
+    mut dealt_events: EventReader<DamageDealtEvent>,
+    mut healed_events: EventReader<HealedEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    mut entries: EventWriter<CombatLogEntry>,
+    names: Query<&Name>,
+) {
+    let now = time.elapsed_secs() - combat_log.started_at;
+    let name_of = |entity: Entity| {
+        names
+            .get(entity)
+            .map_or_else(|_| format!("{entity}"), |name| name.to_string())
+    };
+
+    for event in dealt_events.read() {
+        entries.send(CombatLogEntry {
+            time: now,
+            kind: CombatLogKind::Hit,
+            source: event.source,
+            source_name: name_of(event.source),
+            target: event.target,
+            target_name: name_of(event.target),
+            amount: event.amount,
+            damage_type: Some(event.damage_type),
+            source_position: Some(event.source_position.to_array()),
+        });
+    }
+
+    for event in healed_events.read() {
+        entries.send(CombatLogEntry {
+            time: now,
+            kind: CombatLogKind::Heal,
+            source: event.source,
+            source_name: name_of(event.source),
+            target: event.target,
+            target_name: name_of(event.target),
+            amount: event.amount,
+            damage_type: None,
+            source_position: None,
+        });
+    }
+
+    for event in death_events.read() {
+        entries.send(CombatLogEntry {
+            time: now,
+            kind: CombatLogKind::Kill,
+            source: event.killer,
+            source_name: name_of(event.killer),
+            target: event.entity,
+            target_name: name_of(event.entity),
+            amount: 0.0,
+            damage_type: None,
+            source_position: None,
+        });
+    }
+}
+
+fn store_entries(mut entries: EventReader<CombatLogEntry>, mut combat_log: ResMut<CombatLog>) {
+    combat_log.entries.extend(entries.read().cloned());
+}
+
+fn spawn_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                width: Val::Px(360.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Visibility::Hidden,
+            CombatLogOverlay,
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 13.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                CombatLogText,
+            ));
+        });
+}
+
+fn toggle_overlay(
+    input: Res<ButtonInput<KeyCode>>,
+    mut overlay_query: Query<&mut Visibility, With<CombatLogOverlay>>,
+) {
+    if !input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+    for mut visibility in overlay_query.iter_mut() {
+        visibility.toggle_visible_hidden();
+    }
+}
+
+fn update_overlay(
+    combat_log: Res<CombatLog>,
+    mut text_query: Query<&mut Text, With<CombatLogText>>,
+) {
+    if !combat_log.is_changed() {
+        return;
+    }
+
+    let skip = combat_log.entries.len().saturating_sub(OVERLAY_LINES);
+    let lines: Vec<String> = combat_log.entries[skip..]
+        .iter()
+        .map(|entry| match entry.kind {
+            CombatLogKind::Hit => format!(
+                "[{:>6.1}s] {} hits {} for {:.1} {:?}",
+                entry.time,
+                entry.source_name,
+                entry.target_name,
+                entry.amount,
+                entry.damage_type.unwrap_or_default()
+            ),
+            CombatLogKind::Kill => format!(
+                "[{:>6.1}s] {} kills {}",
+                entry.time, entry.source_name, entry.target_name
+            ),
+            CombatLogKind::Heal => format!(
+                "[{:>6.1}s] {} heals {} for {:.1}",
+                entry.time, entry.source_name, entry.target_name, entry.amount
+            ),
+        })
+        .collect();
+
+    for mut text in text_query.iter_mut() {
+        text.0 = lines.join("\n");
+    }
+}
+
+fn export_combat_log(mut combat_log: ResMut<CombatLog>) {
+    if combat_log.exported {
+        return;
+    }
+    combat_log.exported = true;
+    match write_combat_log(&combat_log) {
+        Ok(path) => info!("Combat log exported to {}", path.display()),
+        Err(err) => error!("Could not export combat log: {err}"),
+    }
+}
+
+fn write_combat_log(combat_log: &CombatLog) -> io::Result<PathBuf> {
+    fs::create_dir_all(EXPORT_DIRECTORY)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = PathBuf::from(EXPORT_DIRECTORY).join(format!("run-{timestamp}.jsonl"));
+
+    let mut writer = BufWriter::new(File::create(&path)?);
+    for entry in &combat_log.entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(path)
+}
@@ -1,38 +1,270 @@
 use bevy::{
     app::{App, Plugin, Update},
     prelude::*,
+    utils::{HashMap, HashSet},
 };
 
-use crate::{collision_state::CollisionState, health::Health, state::{GameState}};
+use crate::{
+    collision_state::CollisionState,
+    events::{DamageDealtEvent, DamageEvent, DamageType, DeathEvent, HealEvent, HealedEvent},
+    health::Health,
+    state::GameState,
+};
 
-use super::{enemy::Enemy, player::Player};
+use super::{
+    enemy::{ai::EnemyBehaviour, Enemy},
+    player::Player,
+};
 
 pub struct DamagePlugin;
 
 impl Plugin for DamagePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (handle_damage).run_if(in_state(GameState::Ongoing)));
+        app.init_resource::<PendingDamage>()
+            .configure_sets(
+                Update,
+                (
+                    DamageSet::Emit,
+                    DamageSet::Collect,
+                    DamageSet::Invulnerability,
+                    DamageSet::Resistance,
+                    DamageSet::Armor,
+                    DamageSet::Apply,
+                    DamageSet::Death,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Ongoing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    contact_damage.in_set(DamageSet::Emit),
+                    collect_damage.in_set(DamageSet::Collect),
+                    (tick_invulnerability, ignore_invulnerable_targets)
+                        .chain()
+                        .in_set(DamageSet::Invulnerability),
+                    apply_resistances.in_set(DamageSet::Resistance),
+                    apply_armor.in_set(DamageSet::Armor),
+                    (apply_damage, apply_heals).in_set(DamageSet::Apply),
+                    despawn_dead_enemies.in_set(DamageSet::Death),
+                ),
+            );
     }
 }
 
-fn handle_damage(
+/// Ordered stages every hit goes through. Systems sending [`DamageEvent`]s
+/// belong in `Emit`, then the hits of the frame are gathered into
+/// [`PendingDamage`] and each following stage may change or drop them before
+/// they are applied to [`Health`]. New damage mechanics add a system to the
+/// stage they belong to.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum DamageSet {
+    Emit,
+    Collect,
+    Invulnerability,
+    Resistance,
+    Armor,
+    Apply,
+    Death,
+}
+
+/// Hits of the current frame that have not been applied yet.
+#[derive(Resource, Default)]
+pub struct PendingDamage(pub Vec<DamageEvent>);
+
+/// Fraction of damage of each type that is ignored, 1.0 being immune.
+#[derive(Component, Default)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+/// Reduces physical damage, 100 armor halves it.
+#[derive(Component)]
+pub struct Armor(pub f32);
+
+/// Seconds an entity can't be damaged again after taking a hit.
+#[derive(Component)]
+pub struct InvulnerabilityFrames(pub f32);
+
+/// Present while an entity ignores all incoming damage.
+#[derive(Component)]
+pub struct Invulnerable(pub Timer);
+
+/// Seconds between two hits of an enemy touching the player
+const CONTACT_DAMAGE_INTERVAL: f32 = 0.5;
+
+fn contact_damage(
     time: Res<Time>,
-    mut player_query: Query<&mut Health, With<Player>>,
-    enemy_query: Query<&Enemy>,
+    player_query: Query<Entity, With<Player>>,
+    enemy_query: Query<(&Enemy, &Transform, &EnemyBehaviour)>,
     mut active_collisions: ResMut<CollisionState>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
-    if let Ok(mut player) = player_query.get_single_mut() {
+    if let Ok(player) = player_query.get_single() {
         for (enemy_entity, elapsed_time) in active_collisions.colliding_entities.iter_mut() {
-            if let Ok(enemy) = enemy_query.get(*enemy_entity) {
-                let delta_time = time.delta_secs();
-                *elapsed_time += delta_time;
-                let damage = enemy.damage_per_second * delta_time;
-                player.apply_damage(damage);
-                println!(
-                    "Player takes {:.2} damage from enemy {:?}. Health is now {:.2}.",
-                    damage, enemy_entity, player.current
-                );
+            if let Ok((enemy, enemy_transform, behaviour)) = enemy_query.get(*enemy_entity) {
+                // Enemies with an attack strike on their own instead
+                if behaviour.attack.is_some() {
+                    continue;
+                }
+                *elapsed_time += time.delta_secs();
+                // Deal the damage of the interval in one hit
+                while *elapsed_time >= CONTACT_DAMAGE_INTERVAL {
+                    *elapsed_time -= CONTACT_DAMAGE_INTERVAL;
+                    damage_events.send(DamageEvent {
+                        source: *enemy_entity,
+                        target: player,
+                        amount: enemy.damage_per_second * CONTACT_DAMAGE_INTERVAL,
+                        damage_type: enemy.damage_type,
+                        source_position: enemy_transform.translation.truncate(),
+                    });
+                }
             }
         }
     }
 }
+
+fn collect_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut pending_damage: ResMut<PendingDamage>,
+) {
+    pending_damage.0.extend(damage_events.read().cloned());
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        invulnerable.0.tick(time.delta());
+        if invulnerable.0.finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn ignore_invulnerable_targets(
+    mut pending_damage: ResMut<PendingDamage>,
+    invulnerable_query: Query<&Invulnerable>,
+) {
+    pending_damage.0.retain(|hit| {
+        invulnerable_query
+            .get(hit.target)
+            .map_or(true, |invulnerable| invulnerable.0.finished())
+    });
+}
+
+fn apply_resistances(
+    mut pending_damage: ResMut<PendingDamage>,
+    resistances_query: Query<&Resistances>,
+) {
+    for hit in pending_damage.0.iter_mut() {
+        if let Ok(resistances) = resistances_query.get(hit.target) {
+            let resistance = resistances.0.get(&hit.damage_type).copied().unwrap_or(0.0);
+            hit.amount *= 1.0 - resistance.clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn apply_armor(mut pending_damage: ResMut<PendingDamage>, armor_query: Query<&Armor>) {
+    for hit in pending_damage.0.iter_mut() {
+        if hit.damage_type != DamageType::Physical {
+            continue;
+        }
+        if let Ok(armor) = armor_query.get(hit.target) {
+            hit.amount *= 100.0 / (100.0 + armor.0.max(0.0));
+        }
+    }
+}
+
+fn apply_damage(
+    mut commands: Commands,
+    mut pending_damage: ResMut<PendingDamage>,
+    mut dealt_events: EventWriter<DamageDealtEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+    mut health_query: Query<(&mut Health, &Transform, Option<&InvulnerabilityFrames>)>,
+) {
+    // Targets that became invulnerable earlier in this frame
+    let mut made_invulnerable = HashSet::new();
+
+    for hit in pending_damage.0.drain(..) {
+        let Ok((mut health, transform, invulnerability_frames)) = health_query.get_mut(hit.target)
+        else {
+            continue;
+        };
+        // Already dead, e.g. hit twice in the same frame
+        if health.is_dead() || hit.amount <= 0.0 || made_invulnerable.contains(&hit.target) {
+            continue;
+        }
+
+        let health_before = health.current;
+        health.apply_damage(hit.amount);
+        dealt_events.send(DamageDealtEvent {
+            source: hit.source,
+            target: hit.target,
+            amount: health_before - health.current,
+            damage_type: hit.damage_type,
+            source_position: hit.source_position,
+        });
+
+        if let Some(invulnerability_frames) = invulnerability_frames {
+            made_invulnerable.insert(hit.target);
+            commands.entity(hit.target).insert(Invulnerable(Timer::from_seconds(
+                invulnerability_frames.0,
+                TimerMode::Once,
+            )));
+        }
+
+        if health.is_dead() {
+            death_events.send(DeathEvent {
+                entity: hit.target,
+                killer: hit.source,
+                position: transform.translation.truncate(),
+            });
+        }
+    }
+}
+
+fn apply_heals(
+    mut heal_events: EventReader<HealEvent>,
+    mut healed_events: EventWriter<HealedEvent>,
+    mut health_query: Query<&mut Health>,
+) {
+    for event in heal_events.read() {
+        let Ok(mut health) = health_query.get_mut(event.target) else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+
+        let healed = health.heal(event.amount);
+        if healed > 0.0 {
+            healed_events.send(HealedEvent {
+                source: event.source,
+                target: event.target,
+                amount: healed,
+            });
+        }
+    }
+}
+
+fn despawn_dead_enemies(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut active_collisions: ResMut<CollisionState>,
+) {
+    for event in death_events.read() {
+        // The player's death is handled by the game over flow instead
+        if enemy_query.get(event.entity).is_err() {
+            continue;
+        }
+
+        debug!(
+            "Enemy {:?} killed by {:?} at {}",
+            event.entity, event.killer, event.position
+        );
+        active_collisions.colliding_entities.remove(&event.entity);
+        commands.entity(event.entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,307 @@
+use bevy::{
+    math::{IVec2, Vec2},
+    prelude::{Component, Entity, EventReader, EventWriter, Query, Res, Transform, With},
+    time::Time,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    events::{DamageDealtEvent, DamageEvent},
+    grid::{tile_to_world, world_to_tile, TILE_SIZE},
+    health::Health,
+    plugins::{map::Map, pathfinding::find_path, player::Player},
+};
+
+use super::Enemy;
+
+/// Which parts of the AI an enemy type uses, set per archetype. Every part is
+/// optional, an empty behaviour chases the player from anywhere on the map
+/// and hurts it on contact.
+#[derive(Component, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnemyBehaviour {
+    /// Distance in pixels at which the player is noticed. Without one the
+    /// enemy always knows where the player is.
+    pub sight_radius: Option<f32>,
+    /// How far from its spawn point the enemy strolls until it notices the
+    /// player, 0 keeps it standing still
+    pub wander_radius: f32,
+    /// Stops next to the player to strike it instead of hurting it on contact
+    pub attack: Option<AttackBehaviour>,
+    /// Fraction of its health under which the enemy runs from the player
+    pub flee_below_health: Option<f32>,
+    /// Distance from its spawn point past which the enemy gives up and walks
+    /// back, ignoring the player until it is home or hurt on the way
+    pub leash_radius: Option<f32>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AttackBehaviour {
+    /// Distance in pixels from which the player can be hit
+    pub range: f32,
+    /// Seconds between two strikes, each dealing the damage of that interval
+    pub cooldown_secs: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AiState {
+    /// Standing still, waiting to wander off again
+    Idle,
+    /// Strolling towards a point near home
+    Wander(Vec2),
+    Chase,
+    Attack,
+    Flee,
+    /// Walking back home around obstacles after straying past the leash
+    Return,
+}
+
+/// Current state of an enemy's AI and what it remembers between frames.
+#[derive(Component)]
+pub struct EnemyAi {
+    pub state: AiState,
+    /// Where the enemy spawned, the centre of its wandering and leash
+    pub home: Vec2,
+    /// Set when hurt, the enemy then chases the player even out of sight
+    provoked: bool,
+    idle_secs: f32,
+    /// Seconds spent walking to the current wander target
+    wander_secs: f32,
+    attack_cooldown_secs: f32,
+    /// Tiles left to walk through on the way home, the next one last
+    return_path: Vec<IVec2>,
+    /// Seconds spent walking home so far
+    return_secs: f32,
+    /// Seconds during which the leash is ignored after being hurt on the way home
+    leash_grace_secs: f32,
+}
+
+impl EnemyAi {
+    pub fn new(home: Vec2) -> Self {
+        Self {
+            state: AiState::Idle,
+            home,
+            provoked: false,
+            idle_secs: 0.0,
+            wander_secs: 0.0,
+            attack_cooldown_secs: 0.0,
+            return_path: Vec::new(),
+            return_secs: 0.0,
+            leash_grace_secs: 0.0,
+        }
+    }
+
+    /// Where a returning enemy walks to next, home itself once on the last
+    /// tile of the path.
+    pub fn return_target(&self) -> Vec2 {
+        self.return_path.last().map_or(self.home, |tile| tile_to_world(*tile))
+    }
+
+    /// Starts walking home from `position`, or settles down where the enemy
+    /// stands when there is no way home.
+    fn start_return(&mut self, map: &Map, position: Vec2) {
+        match find_path(map, world_to_tile(position), world_to_tile(self.home)) {
+            Some(mut path) => {
+                path.reverse();
+                self.return_path = path;
+                self.return_secs = 0.0;
+                self.state = AiState::Return;
+            }
+            None => self.settle(position),
+        }
+    }
+
+    /// Picks a walkable point within `radius` of home to stroll to, or `None`
+    /// when every try landed on impassable terrain.
+    fn wander_target(&self, map: &Map, rng: &mut impl Rng, radius: f32) -> Option<Vec2> {
+        (0..WANDER_TRIES).find_map(|_| {
+            let offset = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU))
+                * rng.gen_range(0.0..radius);
+            let target = self.home + offset;
+            map.tile_at(target).is_walkable().then_some(target)
+        })
+    }
+
+    /// Gives up on the old home and makes `position` the new one.
+    fn settle(&mut self, position: Vec2) {
+        self.home = position;
+        self.return_path.clear();
+        self.state = AiState::Idle;
+    }
+}
+
+/// A chasing enemy loses interest once the player is this many times its
+/// sight radius away
+const LOSE_SIGHT_FACTOR: f32 = 1.5;
+
+/// Seconds an idle enemy waits before wandering off again
+const IDLE_SECS: std::ops::Range<f32> = 1.0..3.0;
+
+/// Distance to a destination at which it counts as reached
+const ARRIVAL_DISTANCE: f32 = TILE_SIZE / 2.0;
+
+/// Random points tried before an enemy gives up on wandering for a while,
+/// when its home is mostly surrounded by water
+const WANDER_TRIES: usize = 8;
+
+/// Seconds a wandering enemy walks towards its target before giving up on it,
+/// in case it can't be reached from where the enemy is
+const WANDER_TIMEOUT_SECS: f32 = 8.0;
+
+/// Seconds a returning enemy keeps trying to get home before settling down
+/// wherever it got stuck
+const RETURN_TIMEOUT_SECS: f32 = 15.0;
+
+/// Seconds a returning enemy that got hurt fights back before the leash pulls
+/// it home again
+const LEASH_GRACE_SECS: f32 = 5.0;
+
+/// Fraction of its speed an enemy wanders at
+pub const WANDER_SPEED_FACTOR: f32 = 0.5;
+
+pub(super) fn provoke_on_damage(
+    mut dealt_events: EventReader<DamageDealtEvent>,
+    mut ai_query: Query<&mut EnemyAi>,
+) {
+    for event in dealt_events.read() {
+        if let Ok(mut ai) = ai_query.get_mut(event.target) {
+            ai.provoked = true;
+            if ai.state == AiState::Return {
+                ai.leash_grace_secs = LEASH_GRACE_SECS;
+            }
+        }
+    }
+}
+
+pub(super) fn update_ai_state(
+    time: Res<Time>,
+    map: Res<Map>,
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(&Transform, &Health, &EnemyBehaviour, &mut EnemyAi), With<Enemy>>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    let mut rng = rand::thread_rng();
+
+    for (transform, health, behaviour, mut ai) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let distance_to_player = position.distance(player_position);
+        let distance_from_home = position.distance(ai.home);
+        ai.idle_secs -= time.delta_secs();
+        ai.wander_secs += time.delta_secs();
+        ai.leash_grace_secs -= time.delta_secs();
+
+        // Once past the leash, nothing but getting hurt matters until the
+        // enemy is home
+        let leashed = ai.leash_grace_secs <= 0.0;
+        let strayed = behaviour
+            .leash_radius
+            .is_some_and(|radius| distance_from_home > radius);
+        let returning = ai.state == AiState::Return && distance_from_home > ARRIVAL_DISTANCE;
+        if leashed && (strayed || returning) {
+            if ai.state != AiState::Return {
+                ai.start_return(&map, position);
+            }
+            ai.provoked = false;
+            ai.return_secs += time.delta_secs();
+            while ai
+                .return_path
+                .last()
+                .is_some_and(|tile| position.distance(tile_to_world(*tile)) <= ARRIVAL_DISTANCE)
+            {
+                ai.return_path.pop();
+            }
+            if ai.return_secs > RETURN_TIMEOUT_SECS {
+                ai.settle(position);
+            }
+            continue;
+        }
+
+        let engaged = matches!(ai.state, AiState::Chase | AiState::Attack | AiState::Flee);
+        let aware = ai.provoked
+            || behaviour.sight_radius.is_none_or(|radius| {
+                let radius = if engaged { radius * LOSE_SIGHT_FACTOR } else { radius };
+                distance_to_player <= radius
+            });
+
+        ai.state = if aware {
+            let hurt = behaviour
+                .flee_below_health
+                .is_some_and(|fraction| health.current <= health.max * fraction);
+            let in_reach = behaviour
+                .attack
+                .is_some_and(|attack| distance_to_player <= attack.range);
+
+            if hurt {
+                AiState::Flee
+            } else if in_reach {
+                AiState::Attack
+            } else {
+                AiState::Chase
+            }
+        } else {
+            let state = ai.state;
+            match state {
+                AiState::Wander(target)
+                    if position.distance(target) > ARRIVAL_DISTANCE
+                        && ai.wander_secs <= WANDER_TIMEOUT_SECS =>
+                {
+                    AiState::Wander(target)
+                }
+                AiState::Idle if ai.idle_secs <= 0.0 && behaviour.wander_radius > 0.0 => {
+                    match ai.wander_target(&map, &mut rng, behaviour.wander_radius) {
+                        Some(target) => {
+                            ai.wander_secs = 0.0;
+                            AiState::Wander(target)
+                        }
+                        None => {
+                            ai.idle_secs = rng.gen_range(IDLE_SECS);
+                            AiState::Idle
+                        }
+                    }
+                }
+                AiState::Idle => AiState::Idle,
+                // Arrived, gave up on getting there, or lost track of the player
+                _ => {
+                    ai.idle_secs = rng.gen_range(IDLE_SECS);
+                    AiState::Idle
+                }
+            }
+        };
+    }
+}
+
+/// Strikes of enemies standing in the `Attack` state, replacing their contact damage.
+pub(super) fn enemy_attack(
+    time: Res<Time>,
+    player_query: Query<Entity, With<Player>>,
+    mut enemy_query: Query<(Entity, &Transform, &Enemy, &EnemyBehaviour, &mut EnemyAi)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+
+    for (entity, transform, enemy, behaviour, mut ai) in enemy_query.iter_mut() {
+        let Some(attack) = behaviour.attack else {
+            continue;
+        };
+        ai.attack_cooldown_secs -= time.delta_secs();
+        if ai.state != AiState::Attack || ai.attack_cooldown_secs > 0.0 {
+            continue;
+        }
+
+        ai.attack_cooldown_secs = attack.cooldown_secs;
+        damage_events.send(DamageEvent {
+            source: entity,
+            target: player,
+            amount: enemy.damage_per_second * attack.cooldown_secs,
+            damage_type: enemy.damage_type,
+            source_position: transform.translation.truncate(),
+        });
+    }
+}
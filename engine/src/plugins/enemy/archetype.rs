@@ -0,0 +1,133 @@
+use bevy::{
+    asset::{io::Reader, Asset, AssetLoader, Handle, LoadContext},
+    color::Color,
+    image::Image,
+    math::Vec2,
+    reflect::TypePath,
+};
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{events::DamageType, plugins::hit_reaction::HitReaction};
+
+use super::ai::EnemyBehaviour;
+
+/// An enemy type, loaded from a `*.enemy.json` file in `assets/enemies`.
+#[derive(Asset, TypePath)]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub sprite: Handle<Image>,
+    pub tint: Color,
+    pub speed: f32,
+    pub damage_per_second: f32,
+    pub damage_type: DamageType,
+    pub health: f32,
+    pub armor: f32,
+    /// Fraction of damage of each type that is ignored
+    pub resistances: HashMap<DamageType, f32>,
+    /// Full width and height of the collider, in pixels
+    pub collider_size: Vec2,
+    pub behaviour: EnemyBehaviour,
+    pub hit_reaction: HitReaction,
+    /// Experience dropped when killed, scaled by the difficulty
+    pub xp: f32,
+    /// Relative chance of this archetype being picked for a spawn
+    pub spawn_weight: f32,
+    /// First wave this archetype can appear in
+    pub min_wave: u32,
+}
+
+/// On-disk layout of an archetype file.
+#[derive(Deserialize)]
+struct EnemyDefinition {
+    name: String,
+    sprite: String,
+    #[serde(default = "default_tint")]
+    tint: [f32; 3],
+    speed: f32,
+    damage_per_second: f32,
+    #[serde(default)]
+    damage_type: DamageType,
+    health: f32,
+    #[serde(default)]
+    armor: f32,
+    #[serde(default)]
+    resistances: HashMap<DamageType, f32>,
+    collider_size: [f32; 2],
+    #[serde(default)]
+    behaviour: EnemyBehaviour,
+    #[serde(default)]
+    hit_reaction: HitReaction,
+    #[serde(default = "default_xp")]
+    xp: f32,
+    #[serde(default = "default_spawn_weight")]
+    spawn_weight: f32,
+    #[serde(default)]
+    min_wave: u32,
+}
+
+fn default_tint() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_xp() -> f32 {
+    1.0
+}
+
+fn default_spawn_weight() -> f32 {
+    1.0
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+#[derive(Debug, Error)]
+pub enum EnemyArchetypeLoaderError {
+    #[error("could not read enemy archetype: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse enemy archetype: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl AssetLoader for EnemyArchetypeLoader {
+    type Asset = EnemyArchetype;
+    type Settings = ();
+    type Error = EnemyArchetypeLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let definition: EnemyDefinition = serde_json::from_slice(&bytes)?;
+
+        let [red, green, blue] = definition.tint;
+        Ok(EnemyArchetype {
+            name: definition.name,
+            // Loading through the context makes the sprite a dependency, so
+            // it is hot reloaded along with the archetype
+            sprite: load_context.load(definition.sprite),
+            tint: Color::srgb(red, green, blue),
+            speed: definition.speed,
+            damage_per_second: definition.damage_per_second,
+            damage_type: definition.damage_type,
+            health: definition.health,
+            armor: definition.armor,
+            resistances: definition.resistances,
+            collider_size: Vec2::from(definition.collider_size),
+            behaviour: definition.behaviour,
+            hit_reaction: definition.hit_reaction,
+            xp: definition.xp,
+            spawn_weight: definition.spawn_weight,
+            min_wave: definition.min_wave,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["enemy.json"]
+    }
+}
@@ -1,110 +1,311 @@
+use ai::{
+    enemy_attack, provoke_on_damage, update_ai_state, AiState, EnemyAi, EnemyBehaviour,
+    WANDER_SPEED_FACTOR,
+};
+use archetype::{EnemyArchetype, EnemyArchetypeLoader};
 use bevy::{
-    app::{App, Plugin, Update},
-    asset::AssetServer,
+    app::{App, Plugin, Startup, Update},
+    asset::{AssetApp, AssetEvent, AssetServer, Assets, Handle, LoadState, LoadedFolder},
     math::{Vec2, Vec3},
+    log::{info, warn},
     prelude::{
-        in_state, Commands, Component, Entity, EventReader, IntoSystemConfigs, OnEnter, Query, Res,
-        Resource, Transform, With,
+        in_state, Commands, Component, Entity, EventReader, IntoSystemConfigs,
+        IntoSystemSetConfigs, Name, Query, Res, Resource, StateScoped, SystemSet, Transform, With,
+        Without,
     },
     sprite::Sprite,
     time::Time,
     window::{PrimaryWindow, Window, WindowResized},
 };
-use bevy_rapier2d::prelude::{ActiveEvents, Collider, GravityScale, LockedAxes, RigidBody};
+use bevy_rapier2d::prelude::{
+    ActiveEvents, Collider, GravityScale, LockedAxes, RigidBody, Velocity,
+};
 
-use crate::state::{AppState, GameState};
+use crate::{
+    events::DamageType,
+    health::Health,
+    state::{AppState, GameState},
+};
+
+use super::{
+    damage::{Armor, DamageSet, Resistances},
+    hit_reaction::{HitReaction, Staggered},
+    map::Map,
+    pathfinding::FlowField,
+    player::Player,
+};
 
-use super::{map::Map, player::Player};
+pub mod ai;
+pub mod archetype;
 
 #[derive(Component)]
 pub struct Enemy {
-   pub damage_per_second: f32
+   pub damage_per_second: f32,
+   pub damage_type: DamageType,
 }
 
-#[derive(Component)]
-struct Velocity(Vec2);
 
 pub struct EnemyPlugin;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(AppState::InGame),
-            (setup, resize_enemy_on_window_resize),
-        )
-        .add_systems(
-            Update,
-            (
-                resize_enemy_on_window_resize,
-                move_toward_player,
-                update_position,
+        app.init_asset::<EnemyArchetype>()
+            .init_asset_loader::<EnemyArchetypeLoader>()
+            .add_systems(Startup, load_archetypes)
+            .add_systems(Update, reload_archetypes)
+            .configure_sets(
+                Update,
+                (EnemySet::Think, EnemySet::Steer, EnemySet::Move)
+                    .chain()
+                    .run_if(in_state(GameState::Ongoing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (update_ai_state, follow_ai_state)
+                        .chain()
+                        .in_set(EnemySet::Think),
+                    apply_terrain.in_set(EnemySet::Move),
+                    enemy_attack.in_set(DamageSet::Emit).after(update_ai_state),
+                ),
             )
-                .run_if(in_state(GameState::Ongoing)),
-        );
+            .add_systems(
+                Update,
+                (resize_enemy_on_window_resize, provoke_on_damage)
+                    .run_if(in_state(GameState::Ongoing)),
+            );
     }
 }
 
-fn setup(
-    mut commands: Commands,
-    map: Res<Map>,
-    asset_server: Res<AssetServer>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-) {
-    // Calculate the center of the map
-    let center_x = map.width as f32 / 2.0;
-    let center_y = map.height as f32 / 2.0;
+/// Ordered stages of enemy movement. `Think` decides where each enemy wants to
+/// go and sets its Rapier [`Velocity`], which systems in `Steer` may adjust
+/// before `Move` fits it to the terrain. Rapier then moves the enemy.
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum EnemySet {
+    Think,
+    Steer,
+    Move,
+}
+
+/// Every enemy archetype found in `assets/enemies`.
+#[derive(Resource)]
+pub struct EnemyArchetypes {
+    folder: Handle<LoadedFolder>,
+}
 
-    let window = window_query.single();
+impl EnemyArchetypes {
+    /// Returns the archetypes once the whole folder has loaded, or `None` while
+    /// it is still loading. Files that failed to load or parse are skipped, so
+    /// one broken archetype doesn't stop the others from spawning.
+    pub fn loaded<'a>(
+        &self,
+        asset_server: &AssetServer,
+        folders: &Assets<LoadedFolder>,
+        archetypes: &'a Assets<EnemyArchetype>,
+    ) -> Option<Vec<(Handle<EnemyArchetype>, &'a EnemyArchetype)>> {
+        let folder = folders.get(&self.folder)?;
+        let mut loaded = Vec::new();
+        for handle in folder
+            .handles
+            .iter()
+            .filter_map(|handle| handle.clone().try_typed::<EnemyArchetype>().ok())
+        {
+            if let Some(archetype) = archetypes.get(&handle) {
+                loaded.push((handle, archetype));
+                continue;
+            }
+            match asset_server.get_load_state(&handle) {
+                Some(LoadState::Failed(err)) => warn!("Skipping enemy archetype: {err}"),
+                _ => return None,
+            }
+        }
+        Some(loaded)
+    }
+}
 
+/// The archetype an enemy was spawned from, along with the difficulty it was
+/// scaled by, so its stats can be rebuilt when the archetype file changes.
+#[derive(Component)]
+pub struct EnemyKind {
+    pub archetype: Handle<EnemyArchetype>,
+    pub difficulty: f32,
+}
+
+fn load_archetypes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(EnemyArchetypes {
+        folder: asset_server.load_folder("enemies"),
+    });
+}
+
+/// Spawns an enemy of the given archetype at `position`. `difficulty` scales
+/// its damage and speed, 1.0 being the archetype as defined.
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    archetype_handle: Handle<EnemyArchetype>,
+    archetype: &EnemyArchetype,
+    window: &Window,
+    position: Vec2,
+    difficulty: f32,
+) -> Entity {
     let scale_factor = calculate_scale(window);
 
-    // Player entity
+    // Enemy entity
     commands
-        .spawn(Sprite::from_image(asset_server.load("enemy.png")))
+        .spawn(Sprite {
+            image: archetype.sprite.clone(),
+            color: archetype.tint,
+            custom_size: Some(archetype.collider_size),
+            ..Default::default()
+        })
         .insert(Enemy {
-            damage_per_second: 10.0
+            damage_per_second: archetype.damage_per_second * difficulty,
+            damage_type: archetype.damage_type,
         })
+        .insert(StateScoped(AppState::InGame))
         .insert(Transform {
-            translation: Vec3::new(center_x, center_y, 1.0), // Position at the center
+            translation: position.extend(1.0),
             scale: Vec3::splat(scale_factor),
             ..Default::default()
         })
-        .insert(Velocity(Vec2::ZERO))
-        .insert(MovementSpeed(90.0))
+        .insert(Velocity::zero())
+        .insert(MovementSpeed(archetype.speed * difficulty.min(2.0)))
+        .insert(Name::new(archetype.name.clone()))
+        .insert(Health::new(archetype.health))
+        .insert(Armor(archetype.armor))
+        .insert(Resistances(archetype.resistances.clone()))
+        .insert(archetype.behaviour)
+        .insert(archetype.hit_reaction)
+        .insert(EnemyAi::new(position))
+        .insert(EnemyKind {
+            archetype: archetype_handle,
+            difficulty,
+        })
         .insert(RigidBody::Dynamic)
-        .insert(Collider::cuboid(16.0, 16.0))
+        .insert(Collider::cuboid(
+            archetype.collider_size.x / 2.0,
+            archetype.collider_size.y / 2.0,
+        ))
         .insert(ActiveEvents::COLLISION_EVENTS)
         .insert(GravityScale(0.0))
-        .insert(LockedAxes::ROTATION_LOCKED);
+        .insert(LockedAxes::ROTATION_LOCKED)
+        .id()
+}
+
+/// Applies edits of archetype files to the enemies already spawned from them.
+#[allow(clippy::type_complexity)]
+fn reload_archetypes(
+    mut asset_events: EventReader<AssetEvent<EnemyArchetype>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    mut enemy_query: Query<(
+        &EnemyKind,
+        &mut Enemy,
+        &mut MovementSpeed,
+        &mut Sprite,
+        &mut Collider,
+        &mut EnemyBehaviour,
+        &mut HitReaction,
+        &mut Armor,
+        &mut Resistances,
+        &mut Health,
+    )>,
+) {
+    for event in asset_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(archetype) = archetypes.get(*id) else {
+            continue;
+        };
+        info!("Reloaded enemy archetype {}", archetype.name);
+
+        for (
+            kind,
+            mut enemy,
+            mut movement_speed,
+            mut sprite,
+            mut collider,
+            mut behaviour,
+            mut hit_reaction,
+            mut armor,
+            mut resistances,
+            mut health,
+        ) in enemy_query.iter_mut()
+        {
+            if kind.archetype.id() != *id {
+                continue;
+            }
+
+            enemy.damage_per_second = archetype.damage_per_second * kind.difficulty;
+            enemy.damage_type = archetype.damage_type;
+            armor.0 = archetype.armor;
+            // Wounded enemies stay as wounded, relative to their new health
+            health.set_max(archetype.health);
+            resistances.0 = archetype.resistances.clone();
+            movement_speed.0 = archetype.speed * kind.difficulty.min(2.0);
+            sprite.image = archetype.sprite.clone();
+            sprite.color = archetype.tint;
+            sprite.custom_size = Some(archetype.collider_size);
+            *collider = Collider::cuboid(
+                archetype.collider_size.x / 2.0,
+                archetype.collider_size.y / 2.0,
+            );
+            *behaviour = archetype.behaviour;
+            *hit_reaction = archetype.hit_reaction;
+        }
+    }
 }
 
 #[derive(Component)]
-pub struct MovementSpeed(f32);
+pub struct MovementSpeed(pub f32);
 
-fn move_toward_player(
-    time: Res<Time>,
+/// Sets the velocity of each enemy to carry out its AI state.
+#[allow(clippy::type_complexity)]
+fn follow_ai_state(
+    flow_field: Res<FlowField>,
     player_query: Query<&Transform, With<Player>>,
-    mut enemy_query: Query<(&Transform, &mut Velocity, &MovementSpeed), With<Enemy>>,
+    mut enemy_query: Query<
+        (&Transform, &mut Velocity, &MovementSpeed, &EnemyAi),
+        (With<Enemy>, Without<Staggered>),
+    >,
 ) {
-    if let Ok(player_transform) = player_query.get_single() {
-        for (enemy_transform, mut velocity, movement_speed) in enemy_query.iter_mut() {
-            // Calculate direction vector towards the player
-            let direction =
-                player_transform.translation.truncate() - enemy_transform.translation.truncate();
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
 
-            // Normalize direction to get a unit vector
-            let normalized_direction = direction.normalize_or_zero();
+    for (enemy_transform, mut velocity, movement_speed, ai) in enemy_query.iter_mut() {
+        let enemy_position = enemy_transform.translation.truncate();
+        let (direction, speed_factor) = match ai.state {
+            AiState::Idle | AiState::Attack => (Vec2::ZERO, 0.0),
+            AiState::Wander(target) => (target - enemy_position, WANDER_SPEED_FACTOR),
+            // Follow the path around obstacles, or head straight for the
+            // player once close enough or out of reach of the flow field
+            AiState::Chase => (
+                flow_field
+                    .direction(enemy_position)
+                    .unwrap_or(player_position - enemy_position),
+                1.0,
+            ),
+            AiState::Flee => (enemy_position - player_position, 1.0),
+            AiState::Return => (ai.return_target() - enemy_position, 1.0),
+        };
 
-            // Update velocity (adjust speed as needed)
-            velocity.0 = normalized_direction * movement_speed.0;
-        }
+        velocity.linvel = direction.normalize_or_zero() * movement_speed.0 * speed_factor;
     }
 }
 
-fn update_position(time: Res<Time>, mut query: Query<(&mut Transform, &Velocity), With<Enemy>>) {
-    for (mut transform, velocity) in query.iter_mut() {
-        // Update position based on velocity and delta time
-        transform.translation += velocity.0.extend(0.0) * time.delta_secs();
+/// Slows enemies down on rough terrain and keeps them out of impassable tiles.
+#[allow(clippy::type_complexity)]
+fn apply_terrain(
+    time: Res<Time>,
+    map: Res<Map>,
+    mut query: Query<(&Transform, &mut Velocity), (With<Enemy>, Without<Staggered>)>,
+) {
+    for (transform, mut velocity) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let terrain_speed = map.tile_at(position).speed_multiplier();
+        velocity.linvel =
+            map.resolve_velocity(position, velocity.linvel * terrain_speed, time.delta_secs());
     }
 }
 
@@ -115,11 +316,11 @@ fn resize_enemy_on_window_resize(
 ) {
     // Process all window resize events
     for _ in resize_events.read() {
-        if let Ok(mut player_transform) = query.get_single_mut() {
-            if let Ok(window) = window_query.get_single() {
-                // Calculate new scale factor based on window dimensions
-                let scale_factor = calculate_scale(window);
-                player_transform.scale = Vec3::splat(scale_factor);
+        if let Ok(window) = window_query.get_single() {
+            // Calculate new scale factor based on window dimensions
+            let scale_factor = calculate_scale(window);
+            for mut enemy_transform in query.iter_mut() {
+                enemy_transform.scale = Vec3::splat(scale_factor);
             }
         }
     }
@@ -1,29 +1,83 @@
-use bevy::{app::{App, Plugin}, asset::{AssetServer, Assets}, color::Color, prelude::{BuildChildren, ChildBuild, Commands, Component, OnEnter, Res, ResMut, Text}, sprite::ColorMaterial, text::{TextColor, TextFont}, ui::{AlignItems, DefaultUiCamera, JustifyContent, Node, Val}, utils::default};
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    prelude::{
+        in_state, BuildChildren, Changed, ChildBuild, ChildBuilder, Commands, Component,
+        IntoSystemConfigs, NextState, OnEnter, Query, Res, ResMut, StateScoped, Text,
+    },
+    text::{TextColor, TextFont},
+    ui::{
+        AlignItems, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node, UiRect,
+        Val,
+    },
+    utils::default,
+};
 
-use crate::state::GameState;
+use crate::{
+    grid::TILE_SIZE,
+    state::{AppState, GameState},
+};
+
+use super::{
+    stats::{record_high_score, HighScores, RunStats},
+    ui,
+};
 
 pub struct GameOverPlugin;
 
 impl Plugin for GameOverPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(GameState::GameOver), show_game_over_screen);
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                show_game_over_screen.after(record_high_score),
+            )
+            .add_systems(Update, game_over_menu.run_if(in_state(GameState::GameOver)));
     }
 }
 
 #[derive(Component)]
 struct GameOverScreen;
 
-fn show_game_over_screen(mut commands: Commands, asset_server: Res<AssetServer>, materials: ResMut<Assets<ColorMaterial>>) {
+/// What pressing a game over button does.
+#[derive(Component, Clone, Copy)]
+enum GameOverButtonAction {
+    Restart,
+    MainMenu,
+}
+
+fn show_game_over_screen(
+    mut commands: Commands,
+    run_stats: Res<RunStats>,
+    high_scores: Res<HighScores>,
+) {
+    let minutes = (run_stats.time_secs / 60.0) as u32;
+    let seconds = run_stats.time_secs as u32 % 60;
+    let summary = [
+        format!("Survived {minutes}:{seconds:02}"),
+        format!("Reached wave {}", run_stats.waves_reached),
+        format!("Enemies killed: {}", run_stats.enemies_killed),
+        format!("Damage dealt: {:.0}", run_stats.damage_dealt),
+        format!("Damage taken: {:.0}", run_stats.damage_taken),
+        format!("Distance travelled: {:.0} tiles", run_stats.distance / TILE_SIZE),
+    ];
+    let rank = match high_scores.last_rank {
+        Some(0) => "New high score!".to_string(),
+        Some(rank) => format!("#{} on the high scores", rank + 1),
+        None => String::new(),
+    };
+
     commands
         .spawn(Node {
-            width: Val::Percent(100.0), 
+            width: Val::Percent(100.0),
             height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..Default::default()
         })
         .insert(GameOverScreen)
+        .insert(StateScoped(GameState::GameOver))
         .with_children(|parent| {
             parent.spawn((
                 Text::new("Game Over"),
@@ -33,5 +87,58 @@ fn show_game_over_screen(mut commands: Commands, asset_server: Res<AssetServer>,
                 },
                 TextColor(Color::srgb(0.9, 0.9, 0.9)),
             ));
+            parent
+                .spawn((
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        margin: UiRect::vertical(Val::Px(12.0)),
+                        padding: UiRect::all(Val::Px(12.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                ))
+                .with_children(|parent| {
+                    for line in summary {
+                        parent.spawn((
+                            Text::new(line),
+                            TextFont {
+                                font_size: 20.0,
+                                ..default()
+                            },
+                            TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                        ));
+                    }
+                });
+            parent.spawn((
+                Text::new(rank),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(1.0, 0.85, 0.4)),
+            ));
+            spawn_button(parent, "Restart", GameOverButtonAction::Restart);
+            spawn_button(parent, "Main Menu", GameOverButtonAction::MainMenu);
         });
-}
\ No newline at end of file
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: &str, action: GameOverButtonAction) {
+    ui::spawn_button(parent, label, action, 260.0, 56.0, 26.0);
+}
+
+/// Leaves the finished run. Every in-game entity is scoped to
+/// `AppState::InGame`, so both the restarted run and the menu start clean.
+fn game_over_menu(
+    mut next_app_state: ResMut<NextState<AppState>>,
+    interaction_query: Query<(&Interaction, &GameOverButtonAction), Changed<Interaction>>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            next_app_state.set(match action {
+                GameOverButtonAction::Restart => AppState::Restarting,
+                GameOverButtonAction::MainMenu => AppState::Menu,
+            });
+        }
+    }
+}
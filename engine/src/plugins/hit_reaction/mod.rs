@@ -0,0 +1,210 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate, Update},
+    color::Color,
+    math::Vec2,
+    prelude::{
+        in_state, Camera, Commands, Component, Entity, EventReader, IntoSystemConfigs, OnExit,
+        Query, Res, ResMut, Resource, Transform, With,
+    },
+    sprite::Sprite,
+    time::{Time, Timer, TimerMode},
+    transform::TransformSystem,
+};
+use bevy_rapier2d::prelude::Velocity;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    events::DamageDealtEvent,
+    health::Health,
+    state::{AppState, GameState},
+};
+
+use super::{damage::DamageSet, map::Map};
+
+pub struct HitReactionPlugin;
+
+impl Plugin for HitReactionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>()
+            .add_systems(OnExit(AppState::InGame), reset_camera_shake)
+            .add_systems(
+                Update,
+                (
+                    react_to_hits.after(DamageSet::Apply),
+                    (recover_from_stagger, fade_flashes),
+                )
+                    .run_if(in_state(GameState::Ongoing)),
+            )
+            .add_systems(
+                PostUpdate,
+                // After the camera followed the player, before its transform is propagated
+                shake_camera
+                    .before(TransformSystem::TransformPropagate)
+                    .run_if(in_state(GameState::Ongoing)),
+            );
+    }
+}
+
+/// How an entity reacts to taking damage. Invulnerability after a hit is
+/// tuned separately with `InvulnerabilityFrames`.
+#[derive(Component, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HitReaction {
+    /// Speed in pixels per second a hit pushes the entity away from its source at
+    pub knockback: f32,
+    /// Seconds the push lasts, during which the entity can't move by itself
+    pub stagger_secs: f32,
+    /// Seconds the sprite flashes for
+    pub flash_secs: f32,
+    /// Camera shake added by each hit, from 0 to 1
+    pub camera_shake: f32,
+}
+
+impl Default for HitReaction {
+    fn default() -> Self {
+        Self {
+            knockback: 300.0,
+            stagger_secs: 0.15,
+            flash_secs: 0.1,
+            camera_shake: 0.0,
+        }
+    }
+}
+
+/// Present while an entity is being knocked back. Movement systems leave its
+/// velocity alone until it is removed.
+#[derive(Component)]
+pub struct Staggered {
+    timer: Timer,
+    /// Velocity the hit gave the entity
+    push: Vec2,
+}
+
+/// Tints the sprite while it lasts, then restores its colour.
+#[derive(Component)]
+struct Flash {
+    timer: Timer,
+    original_color: Color,
+}
+
+const FLASH_COLOR: Color = Color::srgb(1.0, 0.25, 0.25);
+
+/// Trauma of the camera, decaying over time. The shake grows with its
+/// square, so small hits barely move the view while big ones rattle it.
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    pub trauma: f32,
+}
+
+/// Offset of the camera in pixels at full trauma
+const MAX_SHAKE_OFFSET: f32 = 12.0;
+/// Trauma lost per second
+const SHAKE_DECAY: f32 = 1.5;
+
+#[allow(clippy::type_complexity)]
+fn react_to_hits(
+    mut commands: Commands,
+    mut dealt_events: EventReader<DamageDealtEvent>,
+    mut camera_shake: ResMut<CameraShake>,
+    mut target_query: Query<(
+        &HitReaction,
+        &Health,
+        &Transform,
+        &mut Velocity,
+        &Sprite,
+        Option<&Flash>,
+    )>,
+) {
+    for event in dealt_events.read() {
+        let Ok((reaction, health, transform, mut velocity, sprite, flash)) =
+            target_query.get_mut(event.target)
+        else {
+            continue;
+        };
+        // Dead enemies are despawned at the end of the frame
+        if health.is_dead() {
+            continue;
+        }
+
+        let away = transform.translation.truncate() - event.source_position;
+        if reaction.knockback > 0.0 {
+            let push = away.normalize_or(Vec2::Y) * reaction.knockback;
+            velocity.linvel = push;
+            commands.entity(event.target).try_insert(Staggered {
+                timer: Timer::from_seconds(reaction.stagger_secs, TimerMode::Once),
+                push,
+            });
+        }
+
+        if reaction.flash_secs > 0.0 {
+            // Keep the colour from before an ongoing flash
+            let original_color = flash.map_or(sprite.color, |flash| flash.original_color);
+            commands.entity(event.target).try_insert(Flash {
+                timer: Timer::from_seconds(reaction.flash_secs, TimerMode::Once),
+                original_color,
+            });
+        }
+
+        camera_shake.trauma = (camera_shake.trauma + reaction.camera_shake).min(1.0);
+    }
+}
+
+fn recover_from_stagger(
+    mut commands: Commands,
+    time: Res<Time>,
+    map: Res<Map>,
+    mut query: Query<(Entity, &Transform, &mut Velocity, &mut Staggered)>,
+) {
+    for (entity, transform, mut velocity, mut staggered) in query.iter_mut() {
+        staggered.timer.tick(time.delta());
+        if staggered.timer.finished() {
+            velocity.linvel = Vec2::ZERO;
+            commands.entity(entity).remove::<Staggered>();
+            continue;
+        }
+
+        // Ease out of the push, without being shoved onto impassable terrain
+        let pushed = staggered.push * staggered.timer.fraction_remaining();
+        velocity.linvel =
+            map.resolve_velocity(transform.translation.truncate(), pushed, time.delta_secs());
+    }
+}
+
+fn fade_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Flash, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut sprite) in query.iter_mut() {
+        flash.timer.tick(time.delta());
+        if flash.timer.finished() {
+            sprite.color = flash.original_color;
+            commands.entity(entity).remove::<Flash>();
+        } else {
+            sprite.color = FLASH_COLOR;
+        }
+    }
+}
+
+fn reset_camera_shake(mut camera_shake: ResMut<CameraShake>) {
+    camera_shake.trauma = 0.0;
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    mut camera_shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    if camera_shake.trauma <= 0.0 {
+        return;
+    }
+    camera_shake.trauma = (camera_shake.trauma - SHAKE_DECAY * time.delta_secs()).max(0.0);
+
+    let mut rng = rand::thread_rng();
+    let strength = camera_shake.trauma * camera_shake.trauma * MAX_SHAKE_OFFSET;
+    let offset = Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * strength;
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation += offset.extend(0.0);
+    }
+}
@@ -0,0 +1,198 @@
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+/// Parameters the world is generated from. Two maps built from the same
+/// config are identical, so it is all that needs sharing to reproduce a world.
+///
+/// Terrain comes from three noise fields: elevation decides between water,
+/// beaches, land and mountains, then moisture and temperature pick the biome
+/// of the land in between.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MapGenConfig {
+    pub seed: u32,
+    /// Width and height of a chunk, in tiles
+    pub chunk_size: usize,
+    /// Width and height of the world in tiles, centred on the origin. Tiles
+    /// outside of it are deep water. `None` makes the world endless.
+    pub world_size: Option<u32>,
+    /// Number of noise layers summed together, each adding finer detail
+    pub octaves: usize,
+    /// Elevation noise frequency per tile, lower values give larger terrain features
+    pub frequency: f64,
+    /// Moisture and temperature noise frequency per tile, usually lower than
+    /// `frequency` so biomes span several hills
+    pub climate_frequency: f64,
+    /// Elevation below this is deep water
+    pub deep_water_threshold: f64,
+    /// Elevation below this is shallow water
+    pub water_threshold: f64,
+    /// Elevation below this is beach sand
+    pub sand_threshold: f64,
+    /// Elevation above this is stone
+    pub stone_threshold: f64,
+    /// Elevation above this is snow-capped
+    pub snow_threshold: f64,
+    /// Moisture above this grows forest
+    pub forest_threshold: f64,
+    /// Moisture below this leaves bare dirt
+    pub dry_threshold: f64,
+    /// Temperature below this covers the land in snow
+    pub cold_threshold: f64,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        Self {
+            seed: 123456,
+            chunk_size: 16,
+            world_size: None,
+            octaves: 4,
+            frequency: 1.0 / 200.0,
+            climate_frequency: 1.0 / 400.0,
+            deep_water_threshold: -0.6,
+            water_threshold: -0.45,
+            sand_threshold: -0.35,
+            stone_threshold: 0.6,
+            snow_threshold: 0.85,
+            forest_threshold: 0.3,
+            dry_threshold: -0.4,
+            cold_threshold: -0.75,
+        }
+    }
+}
+
+pub const MAX_OCTAVES: usize = 8;
+
+impl MapGenConfig {
+    /// Builds the config from command-line flags such as `--seed 42`, starting
+    /// from the defaults. Unknown flags are ignored so other tools can share
+    /// the command line.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {flag}"))
+            };
+
+            match flag.as_str() {
+                "--seed" => config.seed = parse(&flag, &value()?)?,
+                "--chunk-size" => config.chunk_size = parse(&flag, &value()?)?,
+                "--size" => {
+                    // 0 keeps the world endless
+                    let size: u32 = parse(&flag, &value()?)?;
+                    config.world_size = (size > 0).then_some(size);
+                }
+                "--octaves" => config.octaves = parse(&flag, &value()?)?,
+                "--frequency" => config.frequency = parse(&flag, &value()?)?,
+                "--climate-frequency" => config.climate_frequency = parse(&flag, &value()?)?,
+                "--deep-water-threshold" => {
+                    config.deep_water_threshold = parse(&flag, &value()?)?
+                }
+                "--water-threshold" => config.water_threshold = parse(&flag, &value()?)?,
+                "--sand-threshold" => config.sand_threshold = parse(&flag, &value()?)?,
+                "--stone-threshold" => config.stone_threshold = parse(&flag, &value()?)?,
+                "--snow-threshold" => config.snow_threshold = parse(&flag, &value()?)?,
+                "--forest-threshold" => config.forest_threshold = parse(&flag, &value()?)?,
+                "--dry-threshold" => config.dry_threshold = parse(&flag, &value()?)?,
+                "--cold-threshold" => config.cold_threshold = parse(&flag, &value()?)?,
+                _ => {}
+            }
+        }
+
+        if config.chunk_size == 0 {
+            return Err("--chunk-size must be at least 1".to_string());
+        }
+        config.octaves = config.octaves.clamp(1, MAX_OCTAVES);
+
+        Ok(config)
+    }
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {value:?} for {flag}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_args(args: &[&str]) -> Result<MapGenConfig, String> {
+        MapGenConfig::from_args(args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn no_flags_give_the_defaults() {
+        assert_eq!(from_args(&[]).unwrap(), MapGenConfig::default());
+    }
+
+    #[test]
+    fn flags_override_the_defaults() {
+        let config = from_args(&[
+            "--seed",
+            "42",
+            "--chunk-size",
+            "32",
+            "--size",
+            "500",
+            "--frequency",
+            "0.01",
+            "--cold-threshold",
+            "-0.5",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config,
+            MapGenConfig {
+                seed: 42,
+                chunk_size: 32,
+                world_size: Some(500),
+                frequency: 0.01,
+                cold_threshold: -0.5,
+                ..MapGenConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn size_0_keeps_the_world_endless() {
+        assert_eq!(from_args(&["--size", "0"]).unwrap().world_size, None);
+    }
+
+    #[test]
+    fn unknown_flags_are_ignored() {
+        let config = from_args(&["--fullscreen", "--seed", "7", "extra"]).unwrap();
+
+        assert_eq!(config, MapGenConfig { seed: 7, ..MapGenConfig::default() });
+    }
+
+    #[test]
+    fn missing_value_is_an_error() {
+        assert_eq!(from_args(&["--seed"]), Err("missing value for --seed".to_string()));
+    }
+
+    #[test]
+    fn non_numeric_value_is_an_error() {
+        assert_eq!(
+            from_args(&["--octaves", "many"]),
+            Err("invalid value \"many\" for --octaves".to_string())
+        );
+        assert!(from_args(&["--seed", "-1"]).is_err());
+    }
+
+    #[test]
+    fn chunk_size_0_is_an_error() {
+        assert!(from_args(&["--chunk-size", "0"]).is_err());
+    }
+
+    #[test]
+    fn octaves_are_clamped() {
+        assert_eq!(from_args(&["--octaves", "20"]).unwrap().octaves, MAX_OCTAVES);
+        assert_eq!(from_args(&["--octaves", "0"]).unwrap().octaves, 1);
+    }
+}
@@ -1,100 +1,438 @@
 use bevy::{
-    app::{App, Plugin},
-    asset::AssetServer,
-    math::Vec3,
-    prelude::{Commands, OnEnter, Res, Resource, Transform},
-    sprite::{Sprite, SpriteBundle},
+    app::{App, Plugin, Update},
+    asset::Assets,
+    math::{IVec2, Vec2},
+    prelude::{
+        in_state, BuildChildren, Camera, ChildBuild, Commands, Component, DespawnRecursiveExt,
+        DetectChanges, Entity, IntoSystemConfigs, Mesh, Mesh2d, OnEnter, OnExit, Query, Res, ResMut,
+        Resource, StateScoped, Transform, Visibility, With,
+    },
+    sprite::MeshMaterial2d,
+    utils::HashMap,
 };
-use noise::{NoiseFn, Perlin};
+use bevy_rapier2d::prelude::Collider;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
 
-use crate::state::AppState;
+use crate::{
+    grid::{
+        chunk_to_tile, tile_in_chunk, tile_to_chunk, tile_to_world, world_to_chunk, world_to_tile,
+        TILE_SIZE,
+    },
+    state::AppState,
+};
+
+pub use config::MapGenConfig;
+
+use tilemap::{build_chunk_mesh, load_tileset, Tileset};
+
+use super::save::PendingLoad;
+
+pub mod config;
+mod tilemap;
 
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Map::generate(123456, 200, 200))
-            .add_systems(OnEnter(AppState::InGame), spawn_map);
+        app.init_resource::<MapGenConfig>()
+            .insert_resource(ChunkStreaming::default())
+            .init_resource::<LoadedChunks>()
+            .add_systems(OnEnter(AppState::InGame), (create_map, load_tileset))
+            .add_systems(Update, stream_chunks.run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), forget_chunks);
     }
 }
 
+/// The world map, split into square chunks that are generated on demand from
+/// the config's noise fields. Chunks are cached while they are spawned and
+/// dropped along with their meshes, they can always be generated again from
+/// the seed, so memory doesn't grow as the player keeps walking.
 #[derive(Resource)]
 pub struct Map {
-    pub width: usize,
-    pub height: usize,
+    pub config: MapGenConfig,
+    elevation: Fbm<Perlin>,
+    moisture: Fbm<Perlin>,
+    temperature: Fbm<Perlin>,
+    chunks: HashMap<IVec2, Chunk>,
+}
+
+pub struct Chunk {
     pub tiles: Vec<TileType>, // Flat array of tiles
 }
 
 impl Map {
-    pub fn generate(seed: u32, width: usize, height: usize) -> Self {
-        let perlin = Perlin::new(seed); // Perlin noise for smooth transitions
-
-        let mut tiles = Vec::new();
-
-        for y in 0..height {
-            for x in 0..width {
-                // Normalize x and y to [-1, 1] for Perlin noise
-                let nx = x as f64 / width as f64 - 0.5;
-                let ny = y as f64 / height as f64 - 0.5;
-
-                // Get noise value (ranges from -1 to 1)
-                let noise_value = perlin.get([nx, ny]);
-
-                // Map noise value to tile type
-                let tile_type = if noise_value < -0.2 {
-                    TileType::Water
-                } else if noise_value < 0.2 {
-                    TileType::Grass
-                } else {
-                    TileType::Dirt
-                };
-
-                tiles.push(tile_type);
+    pub fn new(config: &MapGenConfig) -> Self {
+        // Layered Perlin noise for smooth transitions with finer detail on top.
+        // Each field gets its own seed so they don't line up with each other.
+        let field = |seed_offset: u32, frequency: f64| {
+            Fbm::<Perlin>::new(config.seed.wrapping_add(seed_offset))
+                .set_octaves(config.octaves)
+                .set_frequency(frequency)
+        };
+
+        Self {
+            config: config.clone(),
+            elevation: field(0, config.frequency),
+            moisture: field(1, config.climate_frequency),
+            temperature: field(2, config.climate_frequency),
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Generates the tiles of the chunk at `coord` without caching them.
+    pub fn generate_chunk(&self, coord: IVec2) -> Chunk {
+        let size = self.config.chunk_size as i32;
+        let mut tiles = Vec::with_capacity(self.config.chunk_size * self.config.chunk_size);
+
+        for y in 0..size {
+            for x in 0..size {
+                tiles.push(self.generate_tile(
+                    chunk_to_tile(coord, self.config.chunk_size) + IVec2::new(x, y),
+                ));
             }
         }
 
-        Self {
-            width,
-            height,
-            tiles,
+        Chunk { tiles }
+    }
+
+    fn generate_tile(&self, tile: IVec2) -> TileType {
+        // Everything past the edge of a bounded world is deep water
+        if let Some(world_size) = self.config.world_size {
+            let half_size = world_size as i32 / 2;
+            if tile.x < -half_size
+                || tile.y < -half_size
+                || tile.x >= half_size
+                || tile.y >= half_size
+            {
+                return TileType::DeepWater;
+            }
+        }
+
+        // Noise values range from -1 to 1
+        let point = [tile.x as f64, tile.y as f64];
+        let elevation = self.elevation.get(point);
+        let config = &self.config;
+
+        if elevation < config.deep_water_threshold {
+            return TileType::DeepWater;
+        } else if elevation < config.water_threshold {
+            return TileType::ShallowWater;
+        } else if elevation < config.sand_threshold {
+            return TileType::Sand;
+        } else if elevation > config.snow_threshold {
+            return TileType::Snow;
+        } else if elevation > config.stone_threshold {
+            return TileType::Stone;
+        }
+
+        // The climate only decides the biome of the land between beach and mountains
+        let moisture = self.moisture.get(point);
+        let temperature = self.temperature.get(point);
+        if temperature < config.cold_threshold {
+            TileType::Snow
+        } else if moisture > config.forest_threshold {
+            TileType::Forest
+        } else if moisture < config.dry_threshold {
+            TileType::Dirt
+        } else {
+            TileType::Grass
+        }
+    }
+
+    /// Returns the chunk at `coord`, generating and caching it first if needed.
+    pub fn chunk(&mut self, coord: IVec2) -> &Chunk {
+        if !self.chunks.contains_key(&coord) {
+            let chunk = self.generate_chunk(coord);
+            self.chunks.insert(coord, chunk);
+        }
+        &self.chunks[&coord]
+    }
+
+    /// Drops the cached tiles of the chunk at `coord`, if any.
+    pub fn forget_chunk(&mut self, coord: IVec2) {
+        self.chunks.remove(&coord);
+    }
+
+    /// Returns the type of the tile at `tile`. Tiles of chunks that are not
+    /// cached are sampled straight from the noise.
+    pub fn tile_type(&self, tile: IVec2) -> TileType {
+        let size = self.config.chunk_size;
+        match self.chunks.get(&tile_to_chunk(tile, size)) {
+            Some(chunk) => {
+                let local = tile_in_chunk(tile, size);
+                chunk.tiles[local.y as usize * size + local.x as usize]
+            }
+            None => self.generate_tile(tile),
+        }
+    }
+
+    /// Returns the type of the tile under the given world position.
+    pub fn tile_at(&self, position: Vec2) -> TileType {
+        self.tile_type(world_to_tile(position))
+    }
+
+    /// Returns the walkable tile closest to `tile`, searching up to
+    /// `max_distance` tiles away in every direction.
+    pub fn nearest_walkable_tile(&self, tile: IVec2, max_distance: i32) -> Option<IVec2> {
+        (0..=max_distance).find_map(|distance| {
+            // Tiles on the square ring `distance` tiles away, closest first
+            let mut ring: Vec<IVec2> = (-distance..=distance)
+                .flat_map(|y| (-distance..=distance).map(move |x| IVec2::new(x, y)))
+                .filter(|offset| offset.abs().max_element() == distance)
+                .collect();
+            ring.sort_by_key(|offset| offset.length_squared());
+            ring.into_iter()
+                .map(|offset| tile + offset)
+                .find(|tile| self.tile_type(*tile).is_walkable())
+        })
+    }
+
+    /// Returns the part of `velocity` an actor at `position` can keep for the
+    /// next `delta_secs` without stepping onto impassable terrain, sliding
+    /// along it when only one axis is blocked. The colliders of impassable
+    /// tiles only exist in spawned chunks, this keeps actors out everywhere.
+    pub fn resolve_velocity(&self, position: Vec2, velocity: Vec2, delta_secs: f32) -> Vec2 {
+        // Let actors that somehow ended up on impassable terrain walk out of it
+        if !self.tile_at(position).is_walkable() {
+            return velocity;
+        }
+
+        let delta = velocity * delta_secs;
+        if self.tile_at(position + delta).is_walkable() {
+            velocity
+        } else if self.tile_at(position + Vec2::new(delta.x, 0.0)).is_walkable() {
+            Vec2::new(velocity.x, 0.0)
+        } else if self.tile_at(position + Vec2::new(0.0, delta.y)).is_walkable() {
+            Vec2::new(0.0, velocity.y)
+        } else {
+            Vec2::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+impl Map {
+    /// A map laid out from `rows`, top row first, with the bottom left tile on
+    /// the origin. `~` is deep water and any other character grass. Every tile
+    /// around the layout is deep water.
+    pub(crate) fn from_rows(rows: &[&str]) -> Self {
+        let config = MapGenConfig::default();
+        let size = config.chunk_size as i32;
+        let extent = rows.len().max(rows.iter().map(|row| row.len()).max().unwrap_or(0)) as i32;
+        // The layout fits in the chunks right of and above the origin, the
+        // world ends on the chunks left of and below it
+        let chunks = (extent + size - 1) / size;
+        let mut map = Self::new(&MapGenConfig {
+            world_size: Some((2 * chunks * size) as u32),
+            ..config
+        });
+
+        for cy in -chunks..chunks {
+            for cx in -chunks..chunks {
+                let coord = IVec2::new(cx, cy);
+                let tiles = (0..size * size)
+                    .map(|index| {
+                        let tile = chunk_to_tile(coord, map.config.chunk_size)
+                            + IVec2::new(index % size, index / size);
+                        let row = usize::try_from(rows.len() as i32 - 1 - tile.y)
+                            .ok()
+                            .and_then(|y| rows.get(y));
+                        match row.and_then(|row| row.chars().nth(usize::try_from(tile.x).ok()?)) {
+                            None | Some('~') => TileType::DeepWater,
+                            Some(_) => TileType::Grass,
+                        }
+                    })
+                    .collect();
+                map.chunks.insert(coord, Chunk { tiles });
+            }
         }
+
+        map
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TileType {
-    Water,
+    DeepWater,
+    ShallowWater,
+    Sand,
     Grass,
+    Forest,
     Dirt,
+    Stone,
+    Snow,
+}
+
+impl TileType {
+    /// Whether players and enemies can walk on this tile
+    pub fn is_walkable(&self) -> bool {
+        !matches!(self, TileType::DeepWater)
+    }
+
+    /// Multiplier applied to the movement speed of anything walking on this tile
+    pub fn speed_multiplier(&self) -> f32 {
+        match self {
+            // Only matters for something stranded in deep water, which may swim out slowly
+            TileType::DeepWater => 0.4,
+            TileType::ShallowWater => 0.5,
+            TileType::Sand => 0.8,
+            TileType::Grass => 1.0,
+            TileType::Forest => 0.75,
+            TileType::Dirt => 0.7,
+            TileType::Stone => 0.9,
+            TileType::Snow => 0.6,
+        }
+    }
+}
+
+/// Controls how many chunks around the camera are kept spawned.
+#[derive(Resource)]
+pub struct ChunkStreaming {
+    /// Chunks within this many chunks of the camera are spawned
+    pub load_radius: i32,
+    /// Spawned chunks further than this are despawned. Kept larger than
+    /// `load_radius` so chunks on the border don't flicker in and out.
+    pub unload_radius: i32,
+}
+
+impl Default for ChunkStreaming {
+    fn default() -> Self {
+        Self {
+            load_radius: 2,
+            unload_radius: 3,
+        }
+    }
+}
+
+/// A spawned chunk, drawn as a single mesh. Its children are the colliders of
+/// its impassable tiles.
+#[derive(Component)]
+pub struct MapChunk;
+
+#[derive(Resource, Default)]
+struct LoadedChunks(HashMap<IVec2, Entity>);
+
+/// Builds the map of a new session. Systems needing the map on the first
+/// frame run after this one.
+pub fn create_map(
+    mut commands: Commands,
+    mut config: ResMut<MapGenConfig>,
+    pending_load: Option<Res<PendingLoad>>,
+) {
+    // A loaded game continues in the world it was saved in
+    if let Some(pending_load) = pending_load {
+        *config = pending_load.0.map.clone();
+    }
+    commands.insert_resource(Map::new(&config));
+}
+
+/// The chunk entities are scoped to the game and already gone.
+fn forget_chunks(mut loaded_chunks: ResMut<LoadedChunks>) {
+    loaded_chunks.0.clear();
 }
 
-fn spawn_map(mut commands: Commands, asset_server: Res<AssetServer>, map: Res<Map>) {
-    // Load textures for each tile type
-    let water_texture = asset_server.load("water.png");
-    let grass_texture = asset_server.load("grass.png");
-    let dirt_texture = asset_server.load("dirt.png");
-
-    let tile_size = 32.0; // Size of each tile in pixels
-
-    for y in 0..map.height {
-        for x in 0..map.width {
-            let tile_type: &TileType = &map.tiles[y * map.width + x];
-            let texture = match tile_type {
-                TileType::Water => water_texture.clone(),
-                TileType::Grass => grass_texture.clone(),
-                TileType::Dirt => dirt_texture.clone(),
-            };
-
-            let readjusted_x = x as f32 - map.width as f32 / 2.0;
-            let readjusted_y = y as f32 - map.height as f32 / 2.0;
-
-            commands.spawn((
-                Sprite::from_image(texture),
-                Transform {
-                    translation: Vec3::new(readjusted_x * tile_size, readjusted_y * tile_size, 0.0),
-                    scale: Vec3::splat(1.0),
-                    ..Default::default()
-                },
-            ));
+fn stream_chunks(
+    mut commands: Commands,
+    mut map: ResMut<Map>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    streaming: Res<ChunkStreaming>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    tileset: Option<Res<Tileset>>,
+    camera_query: Query<&Transform, With<Camera>>,
+) {
+    let (Some(tileset), Ok(camera_transform)) = (tileset, camera_query.get_single()) else {
+        return;
+    };
+
+    // A new map was inserted, e.g. by loading a save, so the spawned chunks are stale
+    if map.is_added() {
+        for (_, entity) in loaded_chunks.0.drain() {
+            commands.entity(entity).despawn_recursive();
         }
     }
+
+    let center = world_to_chunk(
+        camera_transform.translation.truncate(),
+        map.config.chunk_size,
+    );
+
+    // Despawn chunks that are now too far away, along with their cached tiles
+    loaded_chunks.0.retain(|coord, entity| {
+        let distance = (*coord - center).abs().max_element();
+        if distance > streaming.unload_radius {
+            commands.entity(*entity).despawn_recursive();
+            map.forget_chunk(*coord);
+            false
+        } else {
+            true
+        }
+    });
+
+    // Spawn missing chunks around the camera
+    for y in -streaming.load_radius..=streaming.load_radius {
+        for x in -streaming.load_radius..=streaming.load_radius {
+            let coord = center + IVec2::new(x, y);
+            if loaded_chunks.0.contains_key(&coord) {
+                continue;
+            }
+
+            let entity = spawn_chunk(&mut commands, &mut map, &mut meshes, &tileset, coord);
+            loaded_chunks.0.insert(coord, entity);
+        }
+    }
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    map: &mut Map,
+    meshes: &mut Assets<Mesh>,
+    tileset: &Tileset,
+    coord: IVec2,
+) -> Entity {
+    let chunk_size = map.config.chunk_size;
+    // Cache the chunk first so the mesh reads its tiles rather than regenerating them
+    map.chunk(coord);
+    let mesh = build_chunk_mesh(map, coord);
+    let chunk = &map.chunks[&coord];
+
+    commands
+        .spawn((
+            MapChunk,
+            StateScoped(AppState::InGame),
+            Mesh2d(meshes.add(mesh)),
+            MeshMaterial2d(tileset.material.clone()),
+            Transform::from_translation(
+                tile_to_world(chunk_to_tile(coord, chunk_size)).extend(0.0),
+            ),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            // Block impassable tiles with static colliders, merging horizontal
+            // runs so a lake doesn't need one collider per tile
+            for y in 0..chunk_size {
+                let mut x = 0;
+                while x < chunk_size {
+                    if chunk.tiles[y * chunk_size + x].is_walkable() {
+                        x += 1;
+                        continue;
+                    }
+
+                    let start = x;
+                    while x < chunk_size && !chunk.tiles[y * chunk_size + x].is_walkable() {
+                        x += 1;
+                    }
+
+                    let run_length = (x - start) as f32;
+                    parent.spawn((
+                        Collider::cuboid(run_length * TILE_SIZE / 2.0, TILE_SIZE / 2.0),
+                        Transform::from_xyz(
+                            (start as f32 + (run_length - 1.0) / 2.0) * TILE_SIZE,
+                            y as f32 * TILE_SIZE,
+                            0.0,
+                        ),
+                    ));
+                }
+            }
+        })
+        .id()
 }
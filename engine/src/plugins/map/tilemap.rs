@@ -0,0 +1,197 @@
+use bevy::{
+    asset::{AssetServer, Assets, Handle, RenderAssetUsages},
+    image::{ImageLoaderSettings, ImageSampler},
+    math::{IVec2, Rect, Vec2, Vec3},
+    prelude::{Commands, Mesh, Res, ResMut, Resource},
+    render::mesh::{Indices, PrimitiveTopology},
+    sprite::ColorMaterial,
+};
+
+use crate::grid::{chunk_to_tile, tile_to_world, TILE_SIZE};
+
+use super::{Map, TileType};
+
+/// Image holding the textures of the tiles as a grid of `TILE_SIZE` cells.
+/// The first row holds the full tile of each type, in the order given by
+/// [`TileType::atlas_index`]. Each type then has two rows of transparent
+/// overlays used to blend it into its neighbours, see [`edge_overlay`] and
+/// [`corner_overlay`].
+const TILESET_PATH: &str = "tileset.png";
+const TILESET_COLUMNS: u32 = 16;
+const TILESET_ROWS: u32 = 17;
+
+/// Tile types from the one drawn lowest to the one drawn highest. Where two
+/// types meet, the higher one spills over the edge of the lower one, so sand
+/// covers the shoreline and grass rounds off dirt paths.
+const BLEND_ORDER: [TileType; 8] = [
+    TileType::DeepWater,
+    TileType::ShallowWater,
+    TileType::Sand,
+    TileType::Dirt,
+    TileType::Grass,
+    TileType::Forest,
+    TileType::Stone,
+    TileType::Snow,
+];
+
+/// Neighbour offsets of the edge bits, north, east, south then west
+const EDGES: [IVec2; 4] = [IVec2::Y, IVec2::X, IVec2::NEG_Y, IVec2::NEG_X];
+
+/// Neighbour offsets of the corner bits, north-east, south-east, south-west
+/// then north-west. Corner `i` lies between edges `i` and `i + 1`.
+const CORNERS: [IVec2; 4] = [
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, -1),
+    IVec2::new(-1, 1),
+];
+
+/// Material shared by every chunk mesh.
+#[derive(Resource)]
+pub(super) struct Tileset {
+    pub material: Handle<ColorMaterial>,
+}
+
+impl TileType {
+    /// Cell of the tileset holding this tile's texture
+    pub fn atlas_index(&self) -> u32 {
+        match self {
+            TileType::DeepWater => 0,
+            TileType::ShallowWater => 1,
+            TileType::Sand => 2,
+            TileType::Grass => 3,
+            TileType::Forest => 4,
+            TileType::Dirt => 5,
+            TileType::Stone => 6,
+            TileType::Snow => 7,
+        }
+    }
+}
+
+pub(super) fn load_tileset(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // Linear filtering would blend in the edges of the neighbouring cells
+    let texture = asset_server.load_with_settings(
+        TILESET_PATH,
+        |settings: &mut ImageLoaderSettings| settings.sampler = ImageSampler::nearest(),
+    );
+    commands.insert_resource(Tileset {
+        material: materials.add(texture),
+    });
+}
+
+/// Overlay of `tile_type` covering the edges set in the 4-bit `mask`, one bit
+/// per entry of [`EDGES`].
+fn edge_overlay(tile_type: TileType, mask: u32) -> u32 {
+    TILESET_COLUMNS * (1 + 2 * tile_type.atlas_index()) + mask
+}
+
+/// Overlay of `tile_type` covering the corners set in the 4-bit `mask`, one
+/// bit per entry of [`CORNERS`].
+fn corner_overlay(tile_type: TileType, mask: u32) -> u32 {
+    TILESET_COLUMNS * (2 + 2 * tile_type.atlas_index()) + mask
+}
+
+/// Texture coordinates of a cell of the tileset
+fn atlas_uv(index: u32) -> Rect {
+    let cell = Vec2::new(1.0 / TILESET_COLUMNS as f32, 1.0 / TILESET_ROWS as f32);
+    let min = Vec2::new(
+        (index % TILESET_COLUMNS) as f32,
+        (index / TILESET_COLUMNS) as f32,
+    ) * cell;
+    Rect::from_corners(min, min + cell)
+}
+
+/// Builds one mesh holding the quads of every tile of the chunk at `coord`,
+/// positioned relative to the chunk's first tile. The whole chunk is then
+/// drawn in a single call.
+///
+/// Each tile is drawn whole, then for every higher type in [`BLEND_ORDER`]
+/// that touches it, an edge and a corner overlay picked from bitmasks of the
+/// neighbours of that type. Corners are only set where neither adjacent edge
+/// is, as the edge overlays already cover them.
+pub(super) fn build_chunk_mesh(map: &Map, coord: IVec2) -> Mesh {
+    let chunk_size = map.config.chunk_size as i32;
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+
+    for y in 0..chunk_size {
+        for x in 0..chunk_size {
+            let local = IVec2::new(x, y);
+            let tile = chunk_to_tile(coord, map.config.chunk_size) + local;
+            let tile_type = map.tile_type(tile);
+            let edges = EDGES.map(|offset| map.tile_type(tile + offset));
+            let corners = CORNERS.map(|offset| map.tile_type(tile + offset));
+
+            let mut cells = vec![tile_type.atlas_index()];
+            for overlay_type in BLEND_ORDER
+                .into_iter()
+                .skip_while(|blend_type| *blend_type != tile_type)
+                .skip(1)
+            {
+                let edge_mask = bitmask(edges.map(|edge| edge == overlay_type));
+                let corner_mask = bitmask(
+                    std::array::from_fn(|i| {
+                        corners[i] == overlay_type
+                            && edges[i] != overlay_type
+                            && edges[(i + 1) % 4] != overlay_type
+                    }),
+                );
+
+                if edge_mask != 0 {
+                    cells.push(edge_overlay(overlay_type, edge_mask));
+                }
+                if corner_mask != 0 {
+                    cells.push(corner_overlay(overlay_type, corner_mask));
+                }
+            }
+
+            // Tiles are centred on their position
+            let center = tile_to_world(local);
+            let min = center - TILE_SIZE / 2.0;
+            let max = center + TILE_SIZE / 2.0;
+
+            // Quads of a mesh are drawn in order, so overlays land on top
+            for cell in cells {
+                let uv = atlas_uv(cell);
+                let first = positions.len() as u32;
+                positions.extend([
+                    [min.x, min.y, 0.0],
+                    [max.x, min.y, 0.0],
+                    [max.x, max.y, 0.0],
+                    [min.x, max.y, 0.0],
+                ]);
+                // Image rows go downwards while the world's y axis goes up
+                uvs.extend([
+                    [uv.min.x, uv.max.y],
+                    [uv.max.x, uv.max.y],
+                    [uv.max.x, uv.min.y],
+                    [uv.min.x, uv.min.y],
+                ]);
+                indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+    }
+
+    let normals = vec![Vec3::Z.to_array(); positions.len()];
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Packs the flags into a mask, the first one being the lowest bit
+fn bitmask(flags: [bool; 4]) -> u32 {
+    flags
+        .into_iter()
+        .enumerate()
+        .fold(0, |mask, (bit, set)| mask | (set as u32) << bit)
+}
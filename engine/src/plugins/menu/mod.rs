@@ -1,113 +1,707 @@
+use std::path::PathBuf;
+
 use bevy::{
-    app::{Plugin, Startup, Update},
-    asset::AssetServer,
-    color::{Color, LinearRgba},
+    app::{AppExit, Plugin, Startup, Update},
+    audio::{GlobalVolume, Volume},
+    color::Color,
+    ecs::system::SystemParam,
     input::ButtonInput,
-    math::{ops, Vec3},
+    log::error,
     prelude::{
-        in_state, AppExtStates, BuildChildren, Button, Camera2d, Changed, ChildBuild, Commands,
-        DespawnRecursiveExt, Entity, IntoSystemConfigs, KeyCode, NextState, OnEnter, OnExit, Query,
-        Res, ResMut, Resource, States, Text, Transform, With,
+        in_state, BuildChildren, ChildBuild, ChildBuilder, Commands, Component,
+        DespawnRecursiveExt, Entity, EventReader, EventWriter, IntoSystemConfigs, KeyCode,
+        NextState, OnEnter, Query, Res, ResMut, StateScoped, Text, With,
     },
-    sprite::Sprite,
     text::{TextColor, TextFont},
-    time::Time,
-    ui::{AlignItems, BackgroundColor, Interaction, JustifyContent, Node, Val},
+    ui::{AlignItems, FlexDirection, JustifyContent, Node, UiRect, Val},
     utils::default,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, Window, WindowMode},
+};
+
+use rand::random;
+
+use crate::{plugins::ui::Focused, state::AppState};
+
+use navigation::{activate, focus_hovered, navigate, MenuActivated};
+use settings::{has_vsync, is_fullscreen, load_settings, save_settings};
+
+use super::{
+    map::{config::MAX_OCTAVES, MapGenConfig},
+    pause::PauseSettings,
+    save::{list_saves, read_save, PendingLoad},
+    stats::HighScores,
+    ui,
 };
 
-use crate::state::{AppState, GameState};
+mod navigation;
+mod settings;
 
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app
+        app.add_event::<MenuActivated>()
             // This system runs when we enter `AppState::Menu`, during the `StateTransition` schedule.
             // All systems from the exit schedule of the state we're leaving are run first,
             // and then all systems from the enter schedule of the state we're entering are run second.
+            .add_systems(Startup, load_settings)
             .add_systems(OnEnter(AppState::Menu), setup_menu)
             // By contrast, update systems are stored in the `Update` schedule. They simply
             // check the value of the `State<T>` resource to see if they should run each frame.
-            .add_systems(Update, menu.run_if(in_state(AppState::Menu)))
-            .add_systems(OnExit(AppState::Menu), cleanup_menu);
+            // The screens are scoped to `AppState::Menu`, so leaving it despawns them.
+            .add_systems(
+                Update,
+                (
+                    (navigate, focus_hovered, activate, edit_seed),
+                    menu,
+                    update_setting_labels,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Menu)),
+            )
+            // The pause overlay changes settings too
+            .add_systems(Update, save_settings);
+    }
+}
+
+/// The screens of the menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Screen {
+    Main,
+    NewGame,
+    /// Advanced world generation settings, opened from `NewGame`
+    World,
+    Load,
+    Settings,
+    Credits,
+}
+
+impl Screen {
+    /// Screen that Back leads to, `None` on the main screen
+    fn parent(self) -> Option<Screen> {
+        match self {
+            Screen::Main => None,
+            Screen::World => Some(Screen::NewGame),
+            _ => Some(Screen::Main),
+        }
+    }
+}
+
+/// Root of the screen currently shown, swapped out when a button leads to
+/// another screen.
+#[derive(Component)]
+struct MenuScreen(Screen);
+
+/// What pressing a menu button does.
+#[derive(Component, Clone)]
+enum MenuButtonAction {
+    /// Starts a new run in the world set up on the new game screen
+    Start,
+    Continue,
+    LoadSave(PathBuf),
+    Open(Screen),
+    Back,
+    Quit,
+    /// The seed field, typing digits while it is focused edits the seed
+    EditSeed,
+    RandomSeed,
+    /// Steps a setting down (-1) or up (+1), toggles ignore the direction
+    Adjust(Setting, i32),
+}
+
+/// Anything the menu lets the player change.
+#[derive(Clone, Copy)]
+enum Setting {
+    World(WorldSetting),
+    Volume,
+    Fullscreen,
+    Vsync,
+    PauseOnFocusLoss,
+}
+
+/// Text showing the current value of a setting, kept up to date as it changes.
+#[derive(Component)]
+struct SettingLabel(Setting);
+
+/// Everything the settings of the menu are stored in.
+#[derive(SystemParam)]
+struct Settings<'w, 's> {
+    map_gen_config: ResMut<'w, MapGenConfig>,
+    volume: ResMut<'w, GlobalVolume>,
+    pause_settings: ResMut<'w, PauseSettings>,
+    window_query: Query<'w, 's, &'static mut Window, With<PrimaryWindow>>,
+}
+
+impl Settings<'_, '_> {
+    fn label(&self, setting: Setting) -> String {
+        let window = self.window_query.get_single().ok();
+        match setting {
+            Setting::World(setting) => setting.label(&self.map_gen_config),
+            Setting::Volume => format!("Volume: {:.0}%", self.volume.volume.get() * 100.0),
+            Setting::Fullscreen => on_off("Fullscreen", window.is_some_and(is_fullscreen)),
+            Setting::Vsync => on_off("VSync", window.is_some_and(has_vsync)),
+            Setting::PauseOnFocusLoss => {
+                on_off("Pause on focus loss", self.pause_settings.pause_on_focus_loss)
+            }
+        }
+    }
+
+    fn adjust(&mut self, setting: Setting, step: i32) {
+        match setting {
+            Setting::World(setting) => setting.adjust(&mut self.map_gen_config, step),
+            Setting::Volume => {
+                // Kept on tenths so repeated steps don't drift
+                let volume = (self.volume.volume.get() * 10.0).round() + step as f32;
+                self.volume.volume = Volume::new((volume / 10.0).clamp(0.0, 1.0));
+            }
+            Setting::Fullscreen => {
+                if let Ok(mut window) = self.window_query.get_single_mut() {
+                    window.mode = match window.mode {
+                        WindowMode::Windowed => {
+                            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+                        }
+                        _ => WindowMode::Windowed,
+                    };
+                }
+            }
+            Setting::Vsync => {
+                if let Ok(mut window) = self.window_query.get_single_mut() {
+                    window.present_mode = match window.present_mode {
+                        PresentMode::AutoNoVsync => PresentMode::AutoVsync,
+                        _ => PresentMode::AutoNoVsync,
+                    };
+                }
+            }
+            Setting::PauseOnFocusLoss => {
+                self.pause_settings.pause_on_focus_loss = !self.pause_settings.pause_on_focus_loss;
+            }
+        }
     }
 }
 
-#[derive(Resource)]
-struct MenuData {
-    button_entity: Entity,
+fn on_off(name: &str, on: bool) -> String {
+    format!("{name}: {}", if on { "On" } else { "Off" })
+}
+
+#[derive(Clone, Copy)]
+enum WorldSetting {
+    Seed,
+    Size,
+    Octaves,
+    Frequency,
+    SeaLevel,
+    MountainLevel,
+    Forest,
+    Cold,
+}
+
+impl WorldSetting {
+    fn label(&self, config: &MapGenConfig) -> String {
+        match self {
+            WorldSetting::Seed => format!("Seed: {}", config.seed),
+            WorldSetting::Size => match config.world_size {
+                Some(size) => format!("Size: {size}x{size}"),
+                None => "Size: endless".to_string(),
+            },
+            WorldSetting::Octaves => format!("Octaves: {}", config.octaves),
+            WorldSetting::Frequency => format!("Frequency: {:.4}", config.frequency),
+            WorldSetting::SeaLevel => format!("Sea level: {:.2}", config.water_threshold),
+            WorldSetting::MountainLevel => format!("Mountains: {:.2}", config.stone_threshold),
+            WorldSetting::Forest => format!("Forest above: {:.2}", config.forest_threshold),
+            WorldSetting::Cold => format!("Cold below: {:.2}", config.cold_threshold),
+        }
+    }
+
+    fn adjust(&self, config: &mut MapGenConfig, step: i32) {
+        match self {
+            WorldSetting::Seed => config.seed = config.seed.wrapping_add_signed(step),
+            WorldSetting::Size => {
+                // Steps through endless, 100, 200, ... 1000
+                let size = config.world_size.unwrap_or(0) as i32 + step * 100;
+                config.world_size = (1..=1000).contains(&size).then_some(size as u32);
+            }
+            WorldSetting::Octaves => {
+                config.octaves = config
+                    .octaves
+                    .saturating_add_signed(step as isize)
+                    .clamp(1, MAX_OCTAVES);
+            }
+            WorldSetting::Frequency => {
+                config.frequency = (config.frequency * 1.25f64.powi(step)).clamp(0.0005, 0.1);
+            }
+            // The elevation bands move together so beaches and peaks keep their width
+            WorldSetting::SeaLevel => {
+                let delta = 0.05 * step as f64;
+                if (-1.0..=config.stone_threshold).contains(&(config.sand_threshold + delta)) {
+                    config.deep_water_threshold += delta;
+                    config.water_threshold += delta;
+                    config.sand_threshold += delta;
+                }
+            }
+            WorldSetting::MountainLevel => {
+                let delta = 0.05 * step as f64;
+                if (config.sand_threshold..=1.0).contains(&(config.stone_threshold + delta)) {
+                    config.stone_threshold += delta;
+                    config.snow_threshold += delta;
+                }
+            }
+            WorldSetting::Forest => {
+                config.forest_threshold = (config.forest_threshold + 0.05 * step as f64)
+                    .clamp(config.dry_threshold, 1.0);
+            }
+            WorldSetting::Cold => {
+                config.cold_threshold = (config.cold_threshold + 0.05 * step as f64).clamp(-1.0, 1.0);
+            }
+        }
+    }
 }
 
-const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 
-fn setup_menu(mut commands: Commands) {
-    let button_entity = commands
+/// World settings of the world screen, the seed and size are set on the new
+/// game screen
+const ADVANCED_WORLD_SETTINGS: [WorldSetting; 6] = [
+    WorldSetting::Octaves,
+    WorldSetting::Frequency,
+    WorldSetting::SeaLevel,
+    WorldSetting::MountainLevel,
+    WorldSetting::Forest,
+    WorldSetting::Cold,
+];
+
+/// Key bindings listed on the settings screen
+const CONTROLS: [(&str, &str); 6] = [
+    ("Move", "W A S D"),
+    ("Attack", "Space"),
+    ("Pause", "Escape"),
+    ("Pick upgrade", "1 2 3"),
+    ("Combat log", "Tab"),
+    ("Quicksave", "F5"),
+];
+
+const CREDITS: [&str; 4] = [
+    "Made with Bevy",
+    "Physics by Rapier",
+    "Terrain noise by noise-rs",
+    "Thanks for playing!",
+];
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+const NUMPAD_KEYS: [KeyCode; 10] = [
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+];
+
+/// How many of the high scores the main screen shows
+const MENU_HIGH_SCORES: usize = 5;
+
+fn setup_menu(mut commands: Commands, high_scores: Res<HighScores>) {
+    spawn_main_screen(&mut commands, &high_scores);
+}
+
+fn spawn_screen(
+    commands: &mut Commands,
+    screen: Screen,
+    title: &str,
+    spawn_buttons: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn((
+            Node {
+                // center buttons
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            MenuScreen(screen),
+            StateScoped(AppState::Menu),
+        ))
+        .with_children(|parent| {
+            spawn_text(parent, title, 40.0);
+            spawn_buttons(parent);
+        });
+}
+
+fn spawn_text(parent: &mut ChildBuilder, text: &str, font_size: f32) {
+    parent.spawn((
+        Text::new(text),
+        TextFont {
+            font_size,
+            ..default()
+        },
+        TextColor(Color::srgb(0.9, 0.9, 0.9)),
+    ));
+}
+
+fn spawn_main_screen(commands: &mut Commands, high_scores: &HighScores) {
+    let has_saves = !list_saves().is_empty();
+    spawn_screen(commands, Screen::Main, "Ironveil", |parent| {
+        parent
+            .spawn(Node {
+                align_items: AlignItems::Center,
+                ..default()
+            })
+            .with_children(|parent| {
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Column,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        spawn_button(parent, "New Game", MenuButtonAction::Open(Screen::NewGame));
+                        if has_saves {
+                            spawn_button(parent, "Continue", MenuButtonAction::Continue);
+                            spawn_button(parent, "Load", MenuButtonAction::Open(Screen::Load));
+                        }
+                        spawn_button(parent, "Settings", MenuButtonAction::Open(Screen::Settings));
+                        spawn_button(parent, "Credits", MenuButtonAction::Open(Screen::Credits));
+                        spawn_button(parent, "Quit", MenuButtonAction::Quit);
+                    });
+                if !high_scores.runs.is_empty() {
+                    spawn_high_scores(parent, high_scores);
+                }
+            });
+    })
+}
+
+/// Lists the best runs beside the buttons.
+fn spawn_high_scores(parent: &mut ChildBuilder, high_scores: &HighScores) {
+    parent
         .spawn(Node {
-            // center button
-            width: Val::Percent(100.),
-            height: Val::Percent(100.),
-            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
             align_items: AlignItems::Center,
+            margin: UiRect::left(Val::Px(48.)),
             ..default()
         })
         .with_children(|parent| {
-            parent
-                .spawn((
-                    Button,
-                    Node {
-                        width: Val::Px(150.),
-                        height: Val::Px(65.),
-                        // horizontally center child text
-                        justify_content: JustifyContent::Center,
-                        // vertically center child text
-                        align_items: AlignItems::Center,
+            spawn_text(parent, "High scores", 24.0);
+            for (rank, run) in high_scores.runs.iter().take(MENU_HIGH_SCORES).enumerate() {
+                let minutes = (run.time_secs / 60.0) as u32;
+                let seconds = run.time_secs as u32 % 60;
+                parent.spawn((
+                    Text::new(format!(
+                        "{}. {minutes}:{seconds:02}, wave {}, {} kills",
+                        rank + 1,
+                        run.waves_reached,
+                        run.enemies_killed
+                    )),
+                    TextFont {
+                        font_size: 18.0,
                         ..default()
                     },
-                    BackgroundColor(NORMAL_BUTTON),
-                ))
-                .with_children(|parent| {
-                    parent.spawn((
-                        Text::new("Play"),
-                        TextFont {
-                            font_size: 33.0,
-                            ..default()
-                        },
-                        TextColor(Color::srgb(0.9, 0.9, 0.9)),
-                    ));
+                    TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                ));
+            }
+        });
+}
+
+fn spawn_new_game_screen(commands: &mut Commands) {
+    spawn_screen(commands, Screen::NewGame, "New Game", |parent| {
+        parent
+            .spawn(Node {
+                align_items: AlignItems::Center,
+                ..default()
+            })
+            .with_children(|parent| {
+                spawn_setting_button(
+                    parent,
+                    Setting::World(WorldSetting::Seed),
+                    MenuButtonAction::EditSeed,
+                    260.,
+                );
+                spawn_sized_button(parent, "Random", MenuButtonAction::RandomSeed, 120., 40., 24.);
+            });
+        parent.spawn((
+            Text::new("Type digits while the seed is selected to change it"),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::srgb(0.7, 0.7, 0.7)),
+        ));
+        spawn_setting_row(parent, Setting::World(WorldSetting::Size));
+        spawn_button(parent, "World", MenuButtonAction::Open(Screen::World));
+        spawn_button(parent, "Start", MenuButtonAction::Start);
+        spawn_button(parent, "Back", MenuButtonAction::Back);
+    })
+}
+
+fn spawn_world_screen(commands: &mut Commands) {
+    spawn_screen(commands, Screen::World, "World", |parent| {
+        for setting in ADVANCED_WORLD_SETTINGS {
+            spawn_setting_row(parent, Setting::World(setting));
+        }
+        spawn_button(parent, "Back", MenuButtonAction::Back);
+    })
+}
+
+fn spawn_load_screen(commands: &mut Commands) {
+    let saves = list_saves();
+    spawn_screen(commands, Screen::Load, "Load", |parent| {
+        for save in saves {
+            spawn_button(parent, &save.name, MenuButtonAction::LoadSave(save.path));
+        }
+        spawn_button(parent, "Back", MenuButtonAction::Back);
+    })
+}
+
+fn spawn_settings_screen(commands: &mut Commands) {
+    spawn_screen(commands, Screen::Settings, "Settings", |parent| {
+        parent
+            .spawn(Node {
+                margin: UiRect::vertical(Val::Px(12.)),
+                ..default()
+            })
+            .with_children(|parent| {
+                spawn_settings_column(parent, "Audio", |parent| {
+                    spawn_setting_row(parent, Setting::Volume);
+                });
+                spawn_settings_column(parent, "Video", |parent| {
+                    for setting in [Setting::Fullscreen, Setting::Vsync] {
+                        spawn_setting_button(
+                            parent,
+                            setting,
+                            MenuButtonAction::Adjust(setting, 1),
+                            260.,
+                        );
+                    }
                 });
+                spawn_settings_column(parent, "Controls", |parent| {
+                    spawn_setting_button(
+                        parent,
+                        Setting::PauseOnFocusLoss,
+                        MenuButtonAction::Adjust(Setting::PauseOnFocusLoss, 1),
+                        320.,
+                    );
+                    for (action, keys) in CONTROLS {
+                        parent.spawn((
+                            Text::new(format!("{action}: {keys}")),
+                            TextFont {
+                                font_size: 18.0,
+                                ..default()
+                            },
+                            TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                        ));
+                    }
+                });
+            });
+        spawn_button(parent, "Back", MenuButtonAction::Back);
+    })
+}
+
+fn spawn_settings_column(
+    parent: &mut ChildBuilder,
+    heading: &str,
+    spawn_settings: impl FnOnce(&mut ChildBuilder),
+) {
+    parent
+        .spawn(Node {
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            margin: UiRect::horizontal(Val::Px(18.)),
+            ..default()
         })
-        .id();
-    commands.insert_resource(MenuData { button_entity });
+        .with_children(|parent| {
+            spawn_text(parent, heading, 28.0);
+            spawn_settings(parent);
+        });
 }
 
-fn menu(
-    mut next_app_state: ResMut<NextState<AppState>>,
-    mut next_game_state: ResMut<NextState<GameState>>,
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
-    >,
+fn spawn_credits_screen(commands: &mut Commands) {
+    spawn_screen(commands, Screen::Credits, "Credits", |parent| {
+        for line in CREDITS {
+            spawn_text(parent, line, 24.0);
+        }
+        spawn_button(parent, "Back", MenuButtonAction::Back);
+    })
+}
+
+/// A setting's value with buttons to step it down and up.
+fn spawn_setting_row(parent: &mut ChildBuilder, setting: Setting) {
+    parent
+        .spawn(Node {
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Node {
+                    width: Val::Px(260.),
+                    ..default()
+                },
+                Text::default(),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                SettingLabel(setting),
+            ));
+            spawn_small_button(parent, "-", MenuButtonAction::Adjust(setting, -1));
+            spawn_small_button(parent, "+", MenuButtonAction::Adjust(setting, 1));
+        });
+}
+
+/// A button labelled with a setting's value, like a toggle or a text field.
+fn spawn_setting_button(
+    parent: &mut ChildBuilder,
+    setting: Setting,
+    action: MenuButtonAction,
+    width: f32,
 ) {
-    for (interaction, mut color) in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                *color = PRESSED_BUTTON.into();
-                next_app_state.set(AppState::InGame);
-                next_game_state.set(GameState::Ongoing);
-            }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
-            }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+    parent
+        .spawn((ui::button(width, 40.), action))
+        .with_children(|parent| {
+            parent.spawn((ui::button_text("", 24.0), SettingLabel(setting)));
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: &str, action: MenuButtonAction) {
+    spawn_sized_button(parent, label, action, 220., 65., 33.);
+}
+
+fn spawn_small_button(parent: &mut ChildBuilder, label: &str, action: MenuButtonAction) {
+    spawn_sized_button(parent, label, action, 48., 40., 33.);
+}
+
+fn spawn_sized_button(
+    parent: &mut ChildBuilder,
+    label: &str,
+    action: MenuButtonAction,
+    width: f32,
+    height: f32,
+    font_size: f32,
+) {
+    ui::spawn_button(parent, label, action, width, height, font_size);
+}
+
+/// Typing digits while the seed field is focused appends them to the seed,
+/// Backspace erases its last digit.
+fn edit_seed(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut map_gen_config: ResMut<MapGenConfig>,
+    focused_query: Query<&MenuButtonAction, With<Focused>>,
+) {
+    let Ok(MenuButtonAction::EditSeed) = focused_query.get_single() else {
+        return;
+    };
+    for key in keys.get_just_pressed() {
+        if *key == KeyCode::Backspace {
+            map_gen_config.seed /= 10;
+        } else if let Some(digit) = DIGIT_KEYS.iter().chain(&NUMPAD_KEYS).position(|k| k == key) {
+            // Digits that would overflow the seed are ignored
+            if let Some(seed) = map_gen_config
+                .seed
+                .checked_mul(10)
+                .and_then(|seed| seed.checked_add(digit as u32 % 10))
+            {
+                map_gen_config.seed = seed;
             }
         }
     }
 }
 
-fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>) {
-    commands.entity(menu_data.button_entity).despawn_recursive();
+fn update_setting_labels(settings: Settings, mut label_query: Query<(&mut Text, &SettingLabel)>) {
+    for (mut text, label) in label_query.iter_mut() {
+        let value = settings.label(label.0);
+        if text.0 != value {
+            text.0 = value;
+        }
+    }
+}
+
+fn menu(
+    mut commands: Commands,
+    mut activated_events: EventReader<MenuActivated>,
+    mut settings: Settings,
+    high_scores: Res<HighScores>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut exit_events: EventWriter<AppExit>,
+    screen_query: Query<(Entity, &MenuScreen)>,
+) {
+    // Only the first activation counts, the screen it was on may be gone
+    let Some(MenuActivated(action)) = activated_events.read().next() else {
+        return;
+    };
+    let Ok((screen_entity, MenuScreen(screen))) = screen_query.get_single() else {
+        return;
+    };
+
+    let next_screen = match action {
+        MenuButtonAction::Start => {
+            next_app_state.set(AppState::InGame);
+            None
+        }
+        MenuButtonAction::Continue | MenuButtonAction::LoadSave(_) => {
+            // Saves are listed most recent first
+            let path = match action {
+                MenuButtonAction::LoadSave(path) => Some(path.clone()),
+                _ => list_saves().into_iter().next().map(|save| save.path),
+            };
+            let Some(path) = path else {
+                return;
+            };
+            match read_save(&path) {
+                Ok(save) => {
+                    commands.insert_resource(PendingLoad(save));
+                    // The run starts out `GameState::Ongoing`
+                    next_app_state.set(AppState::InGame);
+                }
+                Err(err) => error!("Could not load {}: {err}", path.display()),
+            }
+            None
+        }
+        MenuButtonAction::Open(screen) => Some(*screen),
+        MenuButtonAction::Back => screen.parent(),
+        MenuButtonAction::Quit => {
+            exit_events.send(AppExit::Success);
+            None
+        }
+        // Typing is handled by `edit_seed`
+        MenuButtonAction::EditSeed => None,
+        MenuButtonAction::RandomSeed => {
+            settings.map_gen_config.seed = random();
+            None
+        }
+        MenuButtonAction::Adjust(setting, step) => {
+            settings.adjust(*setting, *step);
+            None
+        }
+    };
+
+    // Swap the buttons for the ones of the next screen
+    let Some(next_screen) = next_screen else {
+        return;
+    };
+    commands.entity(screen_entity).despawn_recursive();
+    match next_screen {
+        Screen::Main => spawn_main_screen(&mut commands, &high_scores),
+        Screen::NewGame => spawn_new_game_screen(&mut commands),
+        Screen::World => spawn_world_screen(&mut commands),
+        Screen::Load => spawn_load_screen(&mut commands),
+        Screen::Settings => spawn_settings_screen(&mut commands),
+        Screen::Credits => spawn_credits_screen(&mut commands),
+    }
 }
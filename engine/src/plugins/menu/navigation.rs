@@ -0,0 +1,167 @@
+use bevy::{
+    input::{
+        gamepad::{Gamepad, GamepadButton},
+        ButtonInput,
+    },
+    math::Vec2,
+    prelude::{
+        Changed, Commands, Entity, Event, EventWriter, GlobalTransform, Has, KeyCode, Local, Query,
+        Res, With,
+    },
+    ui::Interaction,
+};
+
+use crate::plugins::ui::Focused;
+
+use super::MenuButtonAction;
+
+/// A menu button was clicked, or confirmed while focused.
+#[derive(Event)]
+pub(super) struct MenuActivated(pub MenuButtonAction);
+
+/// How far the left stick has to be pushed to move the focus
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Keys moving the focus, with the direction they move it in. UI positions
+/// grow downwards, so up is negative.
+const DIRECTION_KEYS: [(KeyCode, KeyCode, Vec2); 4] = [
+    (KeyCode::ArrowUp, KeyCode::KeyW, Vec2::NEG_Y),
+    (KeyCode::ArrowDown, KeyCode::KeyS, Vec2::Y),
+    (KeyCode::ArrowLeft, KeyCode::KeyA, Vec2::NEG_X),
+    (KeyCode::ArrowRight, KeyCode::KeyD, Vec2::X),
+];
+
+const DIRECTION_BUTTONS: [(GamepadButton, Vec2); 4] = [
+    (GamepadButton::DPadUp, Vec2::NEG_Y),
+    (GamepadButton::DPadDown, Vec2::Y),
+    (GamepadButton::DPadLeft, Vec2::NEG_X),
+    (GamepadButton::DPadRight, Vec2::X),
+];
+
+/// Moves the focus to the closest button in the direction pressed on the
+/// keyboard, D-pad or left stick. Without a focused button yet, the top left
+/// one is focused.
+pub(super) fn navigate(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    mut stick_held: Local<bool>,
+    button_query: Query<(Entity, &GlobalTransform, Has<Focused>), With<MenuButtonAction>>,
+) {
+    let mut direction = DIRECTION_KEYS
+        .iter()
+        .find(|(key, alternate, _)| keys.any_just_pressed([*key, *alternate]))
+        .map(|(_, _, direction)| *direction);
+
+    for gamepad in gamepad_query.iter() {
+        if let Some((_, pressed)) = DIRECTION_BUTTONS
+            .iter()
+            .find(|(button, _)| gamepad.just_pressed(*button))
+        {
+            direction = Some(*pressed);
+        }
+    }
+
+    // The stick moves the focus once per push, holding it doesn't scroll
+    let stick = gamepad_query
+        .iter()
+        .map(|gamepad| gamepad.left_stick())
+        .find(|stick| stick.length() > STICK_THRESHOLD);
+    if let Some(stick) = stick {
+        if !*stick_held {
+            direction = Some(if stick.x.abs() > stick.y.abs() {
+                Vec2::new(stick.x.signum(), 0.0)
+            } else {
+                Vec2::new(0.0, -stick.y.signum())
+            });
+        }
+    }
+    *stick_held = stick.is_some();
+
+    let Some(direction) = direction else {
+        return;
+    };
+
+    let buttons: Vec<_> = button_query
+        .iter()
+        .map(|(entity, transform, focused)| (entity, transform.translation().truncate(), focused))
+        .collect();
+    let next = match buttons.iter().find(|(_, _, focused)| *focused) {
+        Some((_, from, _)) => buttons
+            .iter()
+            .filter_map(|(entity, position, _)| {
+                let offset = *position - *from;
+                let along = offset.dot(direction);
+                let across = offset.perp_dot(direction).abs();
+                // Only buttons within 45 degrees of the direction are candidates,
+                // the ones in line with the focused button are preferred
+                (along > across).then_some((*entity, along + 2.0 * across))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity),
+        None => buttons
+            .iter()
+            .min_by(|(_, a, _), (_, b, _)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)))
+            .map(|(entity, _, _)| *entity),
+    };
+
+    if let Some(next) = next {
+        for (entity, _, focused) in buttons {
+            if focused {
+                commands.entity(entity).remove::<Focused>();
+            }
+        }
+        commands.entity(next).insert(Focused);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(super) fn focus_hovered(
+    mut commands: Commands,
+    hovered_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<MenuButtonAction>)>,
+    focused_query: Query<Entity, With<Focused>>,
+) {
+    for (entity, interaction) in hovered_query.iter() {
+        if *interaction != Interaction::Hovered || focused_query.contains(entity) {
+            continue;
+        }
+        for focused in focused_query.iter() {
+            commands.entity(focused).remove::<Focused>();
+        }
+        commands.entity(entity).insert(Focused);
+    }
+}
+
+/// Turns clicks, and confirm or back presses on the keyboard and gamepads,
+/// into `MenuActivated` events.
+pub(super) fn activate(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    mut activated_events: EventWriter<MenuActivated>,
+    clicked_query: Query<(&Interaction, &MenuButtonAction), Changed<Interaction>>,
+    focused_query: Query<&MenuButtonAction, With<Focused>>,
+) {
+    for (interaction, action) in clicked_query.iter() {
+        if *interaction == Interaction::Pressed {
+            activated_events.send(MenuActivated(action.clone()));
+        }
+    }
+
+    let confirmed = keys.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter, KeyCode::Space])
+        || gamepad_query
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::South));
+    if confirmed {
+        if let Ok(action) = focused_query.get_single() {
+            activated_events.send(MenuActivated(action.clone()));
+        }
+    }
+
+    let back = keys.just_pressed(KeyCode::Escape)
+        || gamepad_query
+            .iter()
+            .any(|gamepad| gamepad.just_pressed(GamepadButton::East));
+    if back {
+        activated_events.send(MenuActivated(MenuButtonAction::Back));
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fs, io, path::Path};
+
+use bevy::{
+    audio::{GlobalVolume, Volume},
+    log::error,
+    prelude::{Local, Query, Res, ResMut, With},
+    window::{MonitorSelection, PresentMode, PrimaryWindow, Window, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::plugins::pause::PauseSettings;
+
+/// File the settings are kept in, next to the high scores
+const SETTINGS_PATH: &str = "settings.json";
+
+/// The settings kept from one session to the next. World settings aren't,
+/// they only apply to the next new game.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub(super) struct StoredSettings {
+    /// Global volume, from 0 to 1
+    volume: f32,
+    fullscreen: bool,
+    vsync: bool,
+    pause_on_focus_loss: bool,
+}
+
+impl Default for StoredSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            fullscreen: false,
+            vsync: true,
+            pause_on_focus_loss: PauseSettings::default().pause_on_focus_loss,
+        }
+    }
+}
+
+impl StoredSettings {
+    fn current(volume: &GlobalVolume, window: &Window, pause_settings: &PauseSettings) -> Self {
+        Self {
+            volume: volume.volume.get(),
+            fullscreen: is_fullscreen(window),
+            vsync: has_vsync(window),
+            pause_on_focus_loss: pause_settings.pause_on_focus_loss,
+        }
+    }
+
+    fn apply(
+        &self,
+        volume: &mut GlobalVolume,
+        window: Option<&mut Window>,
+        pause_settings: &mut PauseSettings,
+    ) {
+        volume.volume = Volume::new(self.volume.clamp(0.0, 1.0));
+        pause_settings.pause_on_focus_loss = self.pause_on_focus_loss;
+        let Some(window) = window else {
+            return;
+        };
+        window.mode = if self.fullscreen {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+        } else {
+            WindowMode::Windowed
+        };
+        window.present_mode = if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("could not access settings: {0}")]
+    Io(#[from] io::Error),
+    #[error("could not parse settings: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub(super) fn is_fullscreen(window: &Window) -> bool {
+    window.mode != WindowMode::Windowed
+}
+
+pub(super) fn has_vsync(window: &Window) -> bool {
+    !matches!(
+        window.present_mode,
+        PresentMode::AutoNoVsync | PresentMode::Immediate | PresentMode::Mailbox
+    )
+}
+
+fn read_settings(path: &Path) -> Result<StoredSettings, SettingsError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        // Nothing was changed yet
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(StoredSettings::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_settings(path: &Path, settings: &StoredSettings) -> Result<(), SettingsError> {
+    fs::write(path, serde_json::to_vec_pretty(settings)?)?;
+    Ok(())
+}
+
+pub(super) fn load_settings(
+    mut volume: ResMut<GlobalVolume>,
+    mut pause_settings: ResMut<PauseSettings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let settings = match read_settings(Path::new(SETTINGS_PATH)) {
+        Ok(settings) => settings,
+        Err(err) => {
+            error!("Could not load the settings: {err}");
+            return;
+        }
+    };
+    let window = window_query.get_single_mut().ok();
+    settings.apply(
+        &mut volume,
+        window.map(|window| window.into_inner()),
+        &mut pause_settings,
+    );
+}
+
+/// Writes the settings whenever they change, from the menu or the pause
+/// overlay. `stored` is what the file holds, the settings seen on the first
+/// run are the ones just loaded.
+pub(super) fn save_settings(
+    mut stored: Local<Option<StoredSettings>>,
+    volume: Res<GlobalVolume>,
+    pause_settings: Res<PauseSettings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let settings = StoredSettings::current(&volume, window, &pause_settings);
+    if *stored == Some(settings) {
+        return;
+    }
+    if stored.is_some() {
+        if let Err(err) = write_settings(Path::new(SETTINGS_PATH), &settings) {
+            error!("Could not save the settings: {err}");
+        }
+    }
+    *stored = Some(settings);
+}
@@ -1,7 +1,18 @@
 pub mod collision;
+pub mod combat_log;
 pub mod menu;
 pub mod damage;
 pub mod enemy;
 pub mod map;
+pub mod pathfinding;
+pub mod pause;
 pub mod player;
-pub mod game_over;
\ No newline at end of file
+pub mod progression;
+pub mod save;
+pub mod spawner;
+pub mod stats;
+pub mod steering;
+pub mod ui;
+pub mod game_over;
+pub mod hit_reaction;
+pub mod weapon;
\ No newline at end of file
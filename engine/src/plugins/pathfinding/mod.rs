@@ -0,0 +1,378 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    math::{IVec2, Vec2},
+    prelude::{
+        in_state, DetectChanges, IntoSystemConfigs, OnEnter, Query, Res, ResMut, Resource,
+        Transform, With,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    grid::{tile_to_world, world_to_tile},
+    state::{AppState, GameState},
+};
+
+use super::{
+    map::{Map, TileType},
+    player::Player,
+};
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowField>()
+            .add_systems(OnEnter(AppState::InGame), reset_flow_field)
+            .add_systems(
+                Update,
+                update_flow_field.run_if(in_state(GameState::Ongoing)),
+            );
+    }
+}
+
+/// Tiles further than this from the player are left out of the flow field,
+/// enemies out there head straight for the player instead
+const FLOW_FIELD_RADIUS: i32 = 40;
+
+/// How many tiles the search settles per frame. A new field takes a few frames
+/// to complete, during which the previous one keeps being used.
+const SEARCH_BUDGET: usize = 2048;
+
+/// Most tiles `find_path` settles before giving up, bounding the time spent
+/// looking for a tile that can't be reached
+const PATH_SEARCH_LIMIT: usize = 4096;
+
+/// Cost of stepping to an orthogonal neighbour at full speed
+const STRAIGHT_COST: u32 = 10;
+/// Cost of stepping to a diagonal neighbour at full speed, roughly `STRAIGHT_COST * √2`
+const DIAGONAL_COST: u32 = 14;
+
+/// The 8 neighbours of a tile along with the base cost of stepping onto them
+const NEIGHBOURS: [(IVec2, u32); 8] = [
+    (IVec2::new(1, 0), STRAIGHT_COST),
+    (IVec2::new(-1, 0), STRAIGHT_COST),
+    (IVec2::new(0, 1), STRAIGHT_COST),
+    (IVec2::new(0, -1), STRAIGHT_COST),
+    (IVec2::new(1, 1), DIAGONAL_COST),
+    (IVec2::new(1, -1), DIAGONAL_COST),
+    (IVec2::new(-1, 1), DIAGONAL_COST),
+    (IVec2::new(-1, -1), DIAGONAL_COST),
+];
+
+/// Cheapest path cost from every tile around the player to the player's
+/// tile, shared by all enemies so each of them only has to look at its
+/// neighbouring tiles to know where to go. Slow terrain costs more to cross,
+/// so paths prefer grass over wading through shallow water.
+#[derive(Resource, Default)]
+pub struct FlowField {
+    /// Tile the completed field leads to
+    target: Option<IVec2>,
+    costs: HashMap<IVec2, u32>,
+    /// Field being built towards the player's latest tile
+    search: Option<Search>,
+}
+
+impl FlowField {
+    /// Returns the direction to walk from `position` to follow the cheapest
+    /// path to the target, or `None` when the position is on the target tile
+    /// or not covered by the field.
+    pub fn direction(&self, position: Vec2) -> Option<Vec2> {
+        let tile = world_to_tile(position);
+        if self.target == Some(tile) {
+            return None;
+        }
+        let cost = *self.costs.get(&tile)?;
+
+        let next = NEIGHBOURS
+            .iter()
+            .map(|(offset, _)| tile + *offset)
+            .filter(|next| {
+                // Don't cut corners around impassable tiles
+                next.x == tile.x
+                    || next.y == tile.y
+                    || (self.costs.contains_key(&IVec2::new(next.x, tile.y))
+                        && self.costs.contains_key(&IVec2::new(tile.x, next.y)))
+            })
+            .filter_map(|next| Some((next, *self.costs.get(&next)?)))
+            .filter(|(_, next_cost)| *next_cost < cost)
+            .min_by_key(|(_, next_cost)| *next_cost)?
+            .0;
+
+        Some((tile_to_world(next) - position).normalize_or_zero())
+    }
+
+    /// Moves the field towards `target`, starting a new search when the target
+    /// changed and settling up to `budget` tiles of it.
+    fn update(&mut self, map: &Map, target: IVec2, budget: usize) {
+        let searched_target = self.search.as_ref().map(|search| search.target).or(self.target);
+        if searched_target != Some(target) {
+            self.search = Some(Search::new(target));
+        }
+
+        if self.search.as_mut().is_some_and(|search| search.expand(map, budget)) {
+            if let Some(search) = self.search.take() {
+                self.target = Some(search.target);
+                self.costs = search.costs;
+            }
+        }
+    }
+}
+
+/// A Dijkstra search outwards from the target that can be spread over
+/// several frames.
+struct Search {
+    target: IVec2,
+    costs: HashMap<IVec2, u32>,
+    /// Tiles to settle, cheapest first
+    frontier: BinaryHeap<Reverse<(u32, i32, i32)>>,
+}
+
+impl Search {
+    fn new(target: IVec2) -> Self {
+        Self {
+            target,
+            costs: HashMap::from([(target, 0)]),
+            frontier: BinaryHeap::from([Reverse((0, target.x, target.y))]),
+        }
+    }
+
+    /// Settles up to `budget` tiles and returns whether the search is complete.
+    fn expand(&mut self, map: &Map, budget: usize) -> bool {
+        for _ in 0..budget {
+            let Some(Reverse((cost, x, y))) = self.frontier.pop() else {
+                return true;
+            };
+            let tile = IVec2::new(x, y);
+            // Already reached more cheaply after this entry was queued
+            if self.costs.get(&tile).is_some_and(|best| cost > *best) {
+                continue;
+            }
+
+            for (next, step) in steps(map, tile) {
+                if (next - self.target).abs().max_element() > FLOW_FIELD_RADIUS {
+                    continue;
+                }
+
+                let next_cost = cost + step;
+                if self.costs.get(&next).is_none_or(|best| next_cost < *best) {
+                    self.costs.insert(next, next_cost);
+                    self.frontier.push(Reverse((next_cost, next.x, next.y)));
+                }
+            }
+        }
+
+        self.frontier.is_empty()
+    }
+}
+
+/// Finds the cheapest path from `from` to `to` with A*, moving the way the
+/// flow field does. Returns the tiles to walk through in order, `to`
+/// included, or `None` when `to` can't be reached within `PATH_SEARCH_LIMIT`
+/// settled tiles.
+pub fn find_path(map: &Map, from: IVec2, to: IVec2) -> Option<Vec<IVec2>> {
+    // Octile distance, never more than the real cost since no terrain is
+    // faster than full speed
+    let estimate = |tile: IVec2| {
+        let distance = (to - tile).abs();
+        let diagonal = distance.min_element() as u32;
+        let straight = distance.max_element() as u32 - diagonal;
+        straight * STRAIGHT_COST + diagonal * DIAGONAL_COST
+    };
+
+    let mut costs = HashMap::from([(from, 0)]);
+    let mut came_from = HashMap::new();
+    let mut frontier = BinaryHeap::from([Reverse((estimate(from), from.x, from.y))]);
+
+    for _ in 0..PATH_SEARCH_LIMIT {
+        let Reverse((_, x, y)) = frontier.pop()?;
+        let tile = IVec2::new(x, y);
+        if tile == to {
+            let mut path = vec![to];
+            while let Some(previous) = came_from.get(path.last()?) {
+                if *previous == from {
+                    break;
+                }
+                path.push(*previous);
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let cost = costs[&tile];
+        for (next, step) in steps(map, tile) {
+            let next_cost = cost + step;
+            if costs.get(&next).is_none_or(|best| next_cost < *best) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, tile);
+                frontier.push(Reverse((next_cost + estimate(next), next.x, next.y)));
+            }
+        }
+    }
+
+    None
+}
+
+/// The neighbours of `tile` that can be stepped onto, along with the cost of
+/// the step. Diagonal steps cutting the corner of an impassable tile are left
+/// out.
+fn steps(map: &Map, tile: IVec2) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+    let walkable = move |tile: IVec2| map.tile_type(tile).is_walkable();
+
+    NEIGHBOURS.into_iter().filter_map(move |(offset, base_cost)| {
+        let next = tile + offset;
+        let tile_type = map.tile_type(next);
+        let cuts_corner = offset.x != 0
+            && offset.y != 0
+            && !(walkable(tile + IVec2::new(offset.x, 0))
+                && walkable(tile + IVec2::new(0, offset.y)));
+        (tile_type.is_walkable() && !cuts_corner).then(|| (next, step_cost(base_cost, tile_type)))
+    })
+}
+
+/// Cost of stepping onto a tile, higher on terrain that slows walkers down
+fn step_cost(base_cost: u32, tile_type: TileType) -> u32 {
+    (base_cost as f32 / tile_type.speed_multiplier()).round() as u32
+}
+
+fn reset_flow_field(mut flow_field: ResMut<FlowField>) {
+    *flow_field = FlowField::default();
+}
+
+fn update_flow_field(
+    map: Res<Map>,
+    mut flow_field: ResMut<FlowField>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    // A different map was loaded, the old paths lead nowhere
+    if map.is_added() {
+        *flow_field = FlowField::default();
+    }
+
+    let target = world_to_tile(player_transform.translation.truncate());
+    flow_field.update(&map, target, SEARCH_BUDGET);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that `path` walks from `from` one tile at a time over walkable
+    /// tiles without cutting corners.
+    fn assert_walkable(map: &Map, from: IVec2, path: &[IVec2]) {
+        let mut previous = from;
+        for tile in path {
+            let offset = *tile - previous;
+            assert_eq!(offset.abs().max_element(), 1, "{previous} to {tile} is not a step");
+            assert!(map.tile_type(*tile).is_walkable(), "{tile} is not walkable");
+            if offset.x != 0 && offset.y != 0 {
+                assert!(map.tile_type(previous + IVec2::new(offset.x, 0)).is_walkable());
+                assert!(map.tile_type(previous + IVec2::new(0, offset.y)).is_walkable());
+            }
+            previous = *tile;
+        }
+    }
+
+    #[test]
+    fn path_goes_around_a_water_wall() {
+        let map = Map::from_rows(&[
+            ".......",
+            "...~...",
+            "...~...",
+            "...~...",
+            "...~...",
+            "...~...",
+            "...~...",
+        ]);
+        let from = IVec2::new(0, 0);
+        let to = IVec2::new(6, 0);
+
+        let path = find_path(&map, from, to).unwrap();
+
+        assert_eq!(path.last(), Some(&to));
+        assert_walkable(&map, from, &path);
+        // The only way across is over the top of the wall
+        assert!(path.contains(&IVec2::new(3, 6)));
+    }
+
+    #[test]
+    fn path_does_not_cut_corners() {
+        let map = Map::from_rows(&[
+            "..", //
+            ".~",
+        ]);
+
+        let path = find_path(&map, IVec2::new(0, 0), IVec2::new(1, 1)).unwrap();
+
+        assert_eq!(path, [IVec2::new(0, 1), IVec2::new(1, 1)]);
+    }
+
+    #[test]
+    fn diagonal_between_two_water_tiles_is_blocked() {
+        let map = Map::from_rows(&[
+            "~.", //
+            ".~",
+        ]);
+
+        assert_eq!(find_path(&map, IVec2::new(0, 0), IVec2::new(1, 1)), None);
+    }
+
+    #[test]
+    fn unreachable_tile_gives_up_after_the_search_limit() {
+        // An island in a field larger than the search limit, so the search
+        // stops on the limit rather than running out of tiles
+        let mut rows = vec![".".repeat(80); 80];
+        rows[39] = format!("{}~~~{}", ".".repeat(39), ".".repeat(38));
+        rows[40] = format!("{}~.~{}", ".".repeat(39), ".".repeat(38));
+        rows[41] = format!("{}~~~{}", ".".repeat(39), ".".repeat(38));
+        let rows: Vec<_> = rows.iter().map(String::as_str).collect();
+        let map = Map::from_rows(&rows);
+        assert!(rows.concat().len() > PATH_SEARCH_LIMIT);
+
+        assert_eq!(find_path(&map, IVec2::new(0, 0), IVec2::new(40, 39)), None);
+        // The field itself is still crossed
+        assert!(find_path(&map, IVec2::new(0, 0), IVec2::new(79, 79)).is_some());
+    }
+
+    #[test]
+    fn budgeted_flow_field_matches_a_single_pass() {
+        let mut rows = vec![".".repeat(70); 70];
+        for (y, row) in rows.iter_mut().enumerate().skip(10).take(40) {
+            // A wall with a gap, and a pond
+            row.replace_range(30..31, "~");
+            if (20..26).contains(&y) {
+                row.replace_range(45..52, "~~~~~~~");
+            }
+        }
+        let rows: Vec<_> = rows.iter().map(String::as_str).collect();
+        let map = Map::from_rows(&rows);
+        let target = IVec2::new(40, 35);
+
+        let mut single_pass = FlowField::default();
+        single_pass.update(&map, target, usize::MAX);
+        assert_eq!(single_pass.target, Some(target));
+
+        let mut budgeted = FlowField::default();
+        let mut slices = 0;
+        while budgeted.target.is_none() {
+            budgeted.update(&map, target, SEARCH_BUDGET);
+            slices += 1;
+        }
+        assert!(slices > 1, "the field was built in a single slice");
+
+        assert_eq!(budgeted.costs, single_pass.costs);
+        for tile in single_pass.costs.keys() {
+            let position = tile_to_world(*tile);
+            assert_eq!(budgeted.direction(position), single_pass.direction(position));
+        }
+        // Enemies left of the wall walk around it
+        let direction = single_pass.direction(tile_to_world(IVec2::new(29, 30))).unwrap();
+        assert!(direction.y != 0.0);
+    }
+}
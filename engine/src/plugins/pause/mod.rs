@@ -0,0 +1,211 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    input::ButtonInput,
+    prelude::{
+        in_state, state_changed, BuildChildren, Changed, ChildBuild, ChildBuilder, Commands,
+        Component, DespawnRecursiveExt, Entity, EventReader, IntoSystemConfigs, KeyCode, NextState,
+        OnEnter, Query, Res, ResMut, Resource, State, StateScoped, Text, With,
+    },
+    text::{TextColor, TextFont},
+    time::{Time, Virtual},
+    ui::{AlignItems, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node, Val},
+    utils::default,
+    window::WindowFocused,
+};
+use bevy_rapier2d::plugin::RapierConfiguration;
+
+use crate::state::{AppState, GameState};
+
+use super::ui;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseSettings>()
+            .add_systems(Update, freeze_unless_ongoing.run_if(state_changed::<GameState>))
+            .add_systems(
+                Update,
+                (toggle_pause, pause_on_focus_loss).run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(Update, pause_menu.run_if(in_state(GameState::Paused)))
+            .add_systems(OnEnter(GameState::Paused), show_pause_overlay);
+    }
+}
+
+/// Options of the pause subsystem, changed from the pause overlay.
+#[derive(Resource)]
+pub struct PauseSettings {
+    /// Pause when the window loses focus, e.g. when alt-tabbing out
+    pub pause_on_focus_loss: bool,
+}
+
+impl Default for PauseSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_focus_loss: true,
+        }
+    }
+}
+
+/// What pressing a pause overlay button does.
+#[derive(Component, Clone, Copy)]
+enum PauseButtonAction {
+    Resume,
+    Settings,
+    ToggleFocusPause,
+    Back,
+    Restart,
+    QuitToMenu,
+}
+
+#[derive(Component)]
+struct PauseOverlay;
+
+/// Stops the simulation in every state but `Ongoing`, whether the game is
+/// paused, waiting on a level up pick or over. Virtual time stands still, so
+/// timers and `Time` deltas read 0 until play resumes. Once the run is left
+/// the simulation stays frozen until the next one starts.
+fn freeze_unless_ongoing(
+    game_state: Option<Res<State<GameState>>>,
+    mut time: ResMut<Time<Virtual>>,
+    mut config_query: Query<&mut RapierConfiguration>,
+) {
+    let running = game_state.is_some_and(|state| *state.get() == GameState::Ongoing);
+    if running {
+        time.unpause();
+    } else {
+        time.pause();
+    }
+    for mut config in config_query.iter_mut() {
+        config.physics_pipeline_active = running;
+    }
+}
+
+fn toggle_pause(
+    input: Res<ButtonInput<KeyCode>>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match game_state.get() {
+        GameState::Ongoing => next_game_state.set(GameState::Paused),
+        GameState::Paused => next_game_state.set(GameState::Ongoing),
+        // Level ups and game over have screens of their own
+        _ => {}
+    }
+}
+
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    settings: Res<PauseSettings>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let lost_focus = focus_events.read().any(|event| !event.focused);
+    if lost_focus && settings.pause_on_focus_loss && *game_state.get() == GameState::Ongoing {
+        next_game_state.set(GameState::Paused);
+    }
+}
+
+fn show_pause_overlay(mut commands: Commands) {
+    spawn_main_screen(&mut commands);
+}
+
+fn spawn_overlay(
+    commands: &mut Commands,
+    title: &str,
+    spawn_buttons: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            PauseOverlay,
+            StateScoped(GameState::Paused),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(title),
+                TextFont {
+                    font_size: 33.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+            ));
+            spawn_buttons(parent);
+        });
+}
+
+fn spawn_main_screen(commands: &mut Commands) {
+    spawn_overlay(commands, "Paused", |parent| {
+        spawn_button(parent, "Resume", PauseButtonAction::Resume);
+        spawn_button(parent, "Settings", PauseButtonAction::Settings);
+        spawn_button(parent, "Restart", PauseButtonAction::Restart);
+        spawn_button(parent, "Quit to Menu", PauseButtonAction::QuitToMenu);
+    });
+}
+
+fn spawn_settings_screen(commands: &mut Commands, settings: &PauseSettings) {
+    let focus_label = if settings.pause_on_focus_loss {
+        "Pause on focus loss: On"
+    } else {
+        "Pause on focus loss: Off"
+    };
+    spawn_overlay(commands, "Settings", |parent| {
+        spawn_button(parent, focus_label, PauseButtonAction::ToggleFocusPause);
+        spawn_button(parent, "Back", PauseButtonAction::Back);
+    });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, label: &str, action: PauseButtonAction) {
+    ui::spawn_button(parent, label, action, 360.0, 56.0, 26.0);
+}
+
+fn pause_menu(
+    mut commands: Commands,
+    mut settings: ResMut<PauseSettings>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    interaction_query: Query<(&Interaction, &PauseButtonAction), Changed<Interaction>>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    for (interaction, action) in interaction_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match action {
+            PauseButtonAction::Resume => next_game_state.set(GameState::Ongoing),
+            PauseButtonAction::Restart => next_app_state.set(AppState::Restarting),
+            PauseButtonAction::QuitToMenu => next_app_state.set(AppState::Menu),
+            PauseButtonAction::Settings
+            | PauseButtonAction::ToggleFocusPause
+            | PauseButtonAction::Back => {
+                if let PauseButtonAction::ToggleFocusPause = action {
+                    settings.pause_on_focus_loss = !settings.pause_on_focus_loss;
+                }
+
+                // Swap the buttons for the ones of the next screen
+                for overlay in overlay_query.iter() {
+                    commands.entity(overlay).despawn_recursive();
+                }
+                match action {
+                    PauseButtonAction::Back => spawn_main_screen(&mut commands),
+                    _ => spawn_settings_screen(&mut commands, &settings),
+                }
+            }
+        }
+        return;
+    }
+}
@@ -9,11 +9,24 @@ use bevy::{
     time::Time,
     window::{PrimaryWindow, Window, WindowResized},
 };
-use bevy_rapier2d::prelude::{ActiveEvents, Collider, GravityScale, LockedAxes, RigidBody};
+use bevy_rapier2d::prelude::{
+    ActiveEvents, Collider, GravityScale, LockedAxes, RigidBody, Velocity,
+};
 
-use crate::{health::Health, state::{AppState, GameState}};
+use crate::{
+    events::{DamageEvent, DamageType},
+    grid::tile_to_world,
+    health::Health,
+    state::{AppState, GameState},
+};
+
+use super::{
+    damage::{Armor, DamageSet, InvulnerabilityFrames},
+    enemy::Enemy,
+    hit_reaction::{HitReaction, Staggered},
+    map::{create_map, Map},
+};
 
-use super::map::Map;
 
 pub struct PlayerPlugin;
 
@@ -21,13 +34,15 @@ impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             OnEnter(AppState::InGame),
-            (setup, resize_player_on_window_resize),
+            (setup.after(create_map), resize_player_on_window_resize),
         )
         .add_systems(
             Update,
             (
                 check_health,
                 movement,
+                melee_attack.in_set(DamageSet::Emit),
+                fade_swing_effects,
                 camera_follow,
                 resize_player_on_window_resize,
                 update_health_bar,
@@ -41,41 +56,78 @@ impl Plugin for PlayerPlugin {
 pub struct Player;
 
 #[derive(Component)]
-pub struct MovementSpeed(f32);
+pub struct MovementSpeed(pub f32);
+
+/// Direction the player last moved in, kept while standing still.
+#[derive(Component)]
+pub struct Facing(pub Vec2);
 
 #[derive(Component)]
 pub struct HealthBar;
 
+/// A swing hitting every enemy within `range` pixels of the player.
+#[derive(Component)]
+pub struct MeleeAttack {
+    pub damage: f32,
+    pub range: f32,
+    pub cooldown: Timer,
+}
+
+/// Short lived visual of a melee swing.
+#[derive(Component)]
+struct SwingEffect(Timer);
+
+/// How many tiles from the world origin to look for land to spawn on
+const SPAWN_SEARCH_DISTANCE: i32 = 64;
+
 fn setup(
     mut commands: Commands,
-    map: Res<Map>,
     asset_server: Res<AssetServer>,
+    map: Res<Map>,
     window_query: Query<&Window, With<PrimaryWindow>>,
 ) {
-    // Calculate the center of the map
-    let center_x = map.width as f32 / 2.0;
-    let center_y = map.height as f32 / 2.0;
-
     let window = window_query.single();
 
     let scale_factor = calculate_scale(window);
 
+    // Start on the land closest to the world origin
+    let spawn_tile = map
+        .nearest_walkable_tile(IVec2::ZERO, SPAWN_SEARCH_DISTANCE)
+        .unwrap_or_default();
+
     // Player entity
     commands
         .spawn(Sprite::from_image(asset_server.load("player.png")))
         .insert(Player)
+        .insert(StateScoped(AppState::InGame))
+        .insert(Name::new("Player"))
         .insert(Transform {
-            translation: Vec3::new(center_x, center_y, 1.0), // Position at the center
+            translation: tile_to_world(spawn_tile).extend(1.0),
             scale: Vec3::splat(scale_factor),
             ..Default::default()
         })
         .insert(RigidBody::Dynamic)
+        .insert(Velocity::zero())
+        .insert(Facing(Vec2::X))
         .insert(Collider::cuboid(16.0, 16.0))
         .insert(ActiveEvents::COLLISION_EVENTS)
         .insert(GravityScale(0.0))
         .insert(LockedAxes::ROTATION_LOCKED)
         .insert(Health::new(100.0))
+        .insert(Armor(10.0))
+        .insert(InvulnerabilityFrames(0.25))
+        .insert(HitReaction {
+            knockback: 350.0,
+            stagger_secs: 0.15,
+            flash_secs: 0.2,
+            camera_shake: 0.35,
+        })
         .insert(MovementSpeed(200.0))
+        .insert(MeleeAttack {
+            damage: 15.0,
+            range: 56.0,
+            cooldown: Timer::from_seconds(0.4, TimerMode::Once),
+        })
         .with_children(|parent| {
             parent
                 .spawn(Sprite {
@@ -108,19 +160,24 @@ fn update_health_bar(
             if let Ok(mut sprite) = health_bars.get_mut(child) {
                 // Adjust the width of the health bar based on health percentage
                 let health_percentage = health.current.max(0.0) / health.max;
-                sprite.custom_size = Some(Vec2::new(32.0 * health_percentage as f32, 4.0));
+                sprite.custom_size = Some(Vec2::new(32.0 * health_percentage, 4.0));
             }
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn movement(
     time: Res<Time>,
     input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Transform, &MovementSpeed), With<Player>>
+    map: Res<Map>,
+    mut query: Query<
+        (&Transform, &mut Velocity, &mut Facing, &MovementSpeed),
+        (With<Player>, Without<Staggered>),
+    >,
 ) {
-    for (mut transform, movement_speed) in &mut query {
-        let mut direction = Vec3::ZERO;
+    for (transform, mut velocity, mut facing, movement_speed) in &mut query {
+        let mut direction = Vec2::ZERO;
         if input.pressed(KeyCode::KeyA) {
             direction.x -= 1.0;
         }
@@ -134,8 +191,75 @@ fn movement(
             direction.y -= 1.0;
         }
 
-        if direction != Vec3::ZERO {
-            transform.translation += direction.normalize() * movement_speed.0 * time.delta_secs();
+        if direction != Vec2::ZERO {
+            facing.0 = direction.normalize();
+        }
+
+        // Rapier moves the body, so collisions resolve against the velocity
+        let position = transform.translation.truncate();
+        let terrain_speed = map.tile_at(position).speed_multiplier();
+        let wanted = direction.normalize_or_zero() * movement_speed.0 * terrain_speed;
+        velocity.linvel = map.resolve_velocity(position, wanted, time.delta_secs());
+    }
+}
+
+fn melee_attack(
+    mut commands: Commands,
+    time: Res<Time>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut player_query: Query<(Entity, &Transform, &mut MeleeAttack), With<Player>>,
+    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
+) {
+    let Ok((player, player_transform, mut attack)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    attack.cooldown.tick(time.delta());
+    if !attack.cooldown.finished() || !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    attack.cooldown.reset();
+
+    let player_position = player_transform.translation.truncate();
+    for (enemy, enemy_transform) in enemy_query.iter() {
+        if enemy_transform.translation.truncate().distance(player_position) <= attack.range {
+            damage_events.send(DamageEvent {
+                source: player,
+                target: enemy,
+                amount: attack.damage,
+                damage_type: DamageType::Physical,
+                source_position: player_position,
+            });
+        }
+    }
+
+    // The effect is a child of the player, so undo its scale to cover the real range
+    let diameter = attack.range * 2.0 / player_transform.scale.x;
+    commands.entity(player).with_children(|parent| {
+        parent.spawn((
+            Sprite {
+                color: Color::srgba(1.0, 1.0, 1.0, 0.35),
+                custom_size: Some(Vec2::splat(diameter)),
+                ..default()
+            },
+            Transform::from_xyz(0.0, 0.0, -0.5),
+            SwingEffect(Timer::from_seconds(0.12, TimerMode::Once)),
+        ));
+    });
+}
+
+fn fade_swing_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut effects: Query<(Entity, &mut SwingEffect, &mut Sprite)>,
+) {
+    for (entity, mut effect, mut sprite) in effects.iter_mut() {
+        effect.0.tick(time.delta());
+        if effect.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            sprite.color.set_alpha(0.35 * effect.0.fraction_remaining());
         }
     }
 }
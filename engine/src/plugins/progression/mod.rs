@@ -0,0 +1,516 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    input::ButtonInput,
+    math::Vec2,
+    prelude::{
+        in_state, BuildChildren, Button, Changed, ChildBuild, Commands, Component,
+        DespawnRecursiveExt, DetectChanges, Entity, EventReader, IntoSystemConfigs, KeyCode,
+        NextState, OnEnter, OnExit, Parent, Query, Res, ResMut, Resource, StateScoped, Text,
+        Transform, With, Without,
+    },
+    sprite::Sprite,
+    text::{TextColor, TextFont},
+    time::Time,
+    ui::{
+        AlignItems, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node,
+        PositionType, UiRect, Val,
+    },
+    utils::default,
+};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::DeathEvent,
+    health::Health,
+    state::{AppState, GameState},
+};
+
+use super::{
+    damage::DamageSet,
+    enemy::{archetype::EnemyArchetype, EnemyKind},
+    player::{MeleeAttack, MovementSpeed, Player},
+    ui,
+    weapon::{Targeting, Weapon},
+};
+
+pub struct ProgressionPlugin;
+
+impl Plugin for ProgressionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Experience>()
+            .add_systems(OnEnter(AppState::InGame), (reset_experience, spawn_hud))
+            .add_systems(
+                Update,
+                (
+                    // Killed enemies are despawned at the end of `DamageSet::Death`,
+                    // so their archetype is still readable here
+                    drop_xp_orbs.in_set(DamageSet::Death),
+                    (collect_xp_orbs, check_level_up).chain(),
+                )
+                    .run_if(in_state(GameState::Ongoing)),
+            )
+            .add_systems(Update, update_hud.run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(GameState::LevelUp), show_level_up_screen)
+            .add_systems(
+                Update,
+                choose_upgrade.run_if(in_state(GameState::LevelUp)),
+            )
+            .add_systems(OnExit(GameState::LevelUp), forget_upgrade_choices);
+    }
+}
+
+/// Experience of the player in the current run.
+#[derive(Resource)]
+pub struct Experience {
+    pub level: u32,
+    /// Experience gathered towards the next level
+    pub xp: f32,
+    /// Upgrades picked so far, one per level gained. Levels gained beyond
+    /// them still have their upgrade to be picked.
+    pub upgrades: Vec<Upgrade>,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            xp: 0.0,
+            upgrades: Vec::new(),
+        }
+    }
+}
+
+impl Experience {
+    /// Experience needed to go from the current level to the next one
+    pub fn xp_to_next_level(&self) -> f32 {
+        XP_FIRST_LEVEL * XP_GROWTH.powi(self.level as i32 - 1)
+    }
+
+    /// Levels gained whose upgrade hasn't been picked yet
+    fn pending_levels(&self) -> u32 {
+        (self.level - 1).saturating_sub(self.upgrades.len() as u32)
+    }
+
+    /// Distance in pixels from which orbs fly to the player
+    fn pickup_radius(&self) -> f32 {
+        let magnets = self
+            .upgrades
+            .iter()
+            .filter(|upgrade| **upgrade == Upgrade::Magnet)
+            .count();
+        PICKUP_RADIUS + MAGNET_BONUS * magnets as f32
+    }
+
+    fn gain(&mut self, xp: f32) {
+        self.xp += xp;
+        while self.xp >= self.xp_to_next_level() {
+            self.xp -= self.xp_to_next_level();
+            self.level += 1;
+        }
+    }
+}
+
+/// Experience needed to reach level 2
+const XP_FIRST_LEVEL: f32 = 5.0;
+/// How much more experience each level needs than the previous one
+const XP_GROWTH: f32 = 1.4;
+
+const PICKUP_RADIUS: f32 = 96.0;
+/// Pickup radius added by each `Magnet` upgrade
+const MAGNET_BONUS: f32 = 48.0;
+/// Speed in pixels per second at which orbs fly to the player
+const ORB_SPEED: f32 = 420.0;
+/// Distance to the player at which an orb is collected
+const COLLECT_DISTANCE: f32 = 16.0;
+const ORB_COLOR: Color = Color::srgb(0.3, 0.9, 1.0);
+
+/// How many upgrades are offered on each level up
+const UPGRADE_CHOICES: usize = 3;
+
+/// A permanent boost to the player, picked on level up.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Upgrade {
+    MaxHealth,
+    Speed,
+    Damage,
+    AttackSpeed,
+    Reach,
+    Magnet,
+}
+
+impl Upgrade {
+    const ALL: [Upgrade; 6] = [
+        Upgrade::MaxHealth,
+        Upgrade::Speed,
+        Upgrade::Damage,
+        Upgrade::AttackSpeed,
+        Upgrade::Reach,
+        Upgrade::Magnet,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Upgrade::MaxHealth => "Vitality",
+            Upgrade::Speed => "Swiftness",
+            Upgrade::Damage => "Strength",
+            Upgrade::AttackSpeed => "Frenzy",
+            Upgrade::Reach => "Reach",
+            Upgrade::Magnet => "Magnet",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Upgrade::MaxHealth => "+20 max health, healed by as much",
+            Upgrade::Speed => "+10% movement speed",
+            Upgrade::Damage => "+5 melee damage, +20% weapon damage",
+            Upgrade::AttackSpeed => "Swing and fire 15% more often",
+            Upgrade::Reach => "+8 melee range, +10% weapon range",
+            Upgrade::Magnet => "Collect experience from further away",
+        }
+    }
+
+    /// Applies the upgrade to the player's stats. `Magnet` only changes the
+    /// pickup radius of [`Experience`].
+    pub fn apply(&self, health: &mut Health, speed: &mut MovementSpeed, attack: &mut MeleeAttack) {
+        match self {
+            Upgrade::MaxHealth => {
+                health.max += 20.0;
+                health.current += 20.0;
+            }
+            Upgrade::Speed => speed.0 *= 1.1,
+            Upgrade::Damage => attack.damage += 5.0,
+            Upgrade::AttackSpeed => {
+                let cooldown = attack.cooldown.duration().mul_f32(0.85);
+                attack.cooldown.set_duration(cooldown);
+            }
+            Upgrade::Reach => attack.range += 8.0,
+            Upgrade::Magnet => {}
+        }
+    }
+
+    /// Applies the upgrade to one of the player's weapons. Only `Damage`,
+    /// `AttackSpeed` and `Reach` affect weapons.
+    pub fn apply_to_weapon(&self, weapon: &mut Weapon) {
+        match self {
+            Upgrade::Damage => weapon.damage *= 1.2,
+            Upgrade::AttackSpeed => {
+                let cooldown = weapon.cooldown.duration().mul_f32(0.85);
+                weapon.cooldown.set_duration(cooldown);
+            }
+            Upgrade::Reach => match &mut weapon.targeting {
+                Targeting::Nearest { range } => *range *= 1.1,
+                // Knives fly further before falling
+                Targeting::Movement => weapon.projectile.lifetime_secs *= 1.1,
+                Targeting::Orbit { radius } => *radius *= 1.1,
+            },
+            Upgrade::MaxHealth | Upgrade::Speed | Upgrade::Magnet => {}
+        }
+    }
+}
+
+/// Experience dropped by a killed enemy, waiting to be collected.
+#[derive(Component)]
+pub struct XpOrb {
+    pub value: f32,
+}
+
+/// The upgrades offered on the level up screen, in the order of their keys.
+#[derive(Resource)]
+struct UpgradeChoices(Vec<Upgrade>);
+
+#[derive(Component)]
+struct LevelUpScreen;
+
+#[derive(Component)]
+struct UpgradeButton(Upgrade);
+
+#[derive(Component)]
+struct ExperienceText;
+
+#[derive(Component)]
+struct ExperienceBar;
+
+
+const CHOICE_KEYS: [KeyCode; UPGRADE_CHOICES] = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+
+fn reset_experience(mut experience: ResMut<Experience>) {
+    *experience = Experience::default();
+}
+
+fn drop_xp_orbs(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    enemy_query: Query<&EnemyKind>,
+) {
+    for event in death_events.read() {
+        let Ok(kind) = enemy_query.get(event.entity) else {
+            continue;
+        };
+        let Some(archetype) = archetypes.get(&kind.archetype) else {
+            continue;
+        };
+        if archetype.xp <= 0.0 {
+            continue;
+        }
+
+        commands.spawn((
+            Sprite {
+                color: ORB_COLOR,
+                custom_size: Some(Vec2::splat(8.0)),
+                ..default()
+            },
+            Transform::from_translation(event.position.extend(0.5)),
+            XpOrb {
+                value: archetype.xp * kind.difficulty,
+            },
+            StateScoped(AppState::InGame),
+        ));
+    }
+}
+
+/// Pulls the orbs within the pickup radius towards the player and collects
+/// the ones that reached it.
+fn collect_xp_orbs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut experience: ResMut<Experience>,
+    player_query: Query<&Transform, With<Player>>,
+    mut orb_query: Query<(Entity, &mut Transform, &XpOrb), Without<Player>>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    let pickup_radius = experience.pickup_radius();
+
+    for (entity, mut transform, orb) in orb_query.iter_mut() {
+        let offset = player_position - transform.translation.truncate();
+        let distance = offset.length();
+        if distance <= COLLECT_DISTANCE {
+            experience.gain(orb.value);
+            commands.entity(entity).despawn();
+        } else if distance <= pickup_radius {
+            let step = (ORB_SPEED * time.delta_secs()).min(distance);
+            transform.translation += (offset / distance * step).extend(0.0);
+        }
+    }
+}
+
+fn check_level_up(
+    experience: Res<Experience>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if experience.pending_levels() > 0 {
+        next_game_state.set(GameState::LevelUp);
+    }
+}
+
+fn show_level_up_screen(mut commands: Commands, experience: Res<Experience>) {
+    spawn_level_up_screen(&mut commands, &experience);
+}
+
+/// Offers a random pick of distinct upgrades for the oldest level whose
+/// upgrade hasn't been picked yet.
+fn spawn_level_up_screen(commands: &mut Commands, experience: &Experience) {
+    let choices: Vec<Upgrade> = Upgrade::ALL
+        .choose_multiple(&mut rand::thread_rng(), UPGRADE_CHOICES)
+        .copied()
+        .collect();
+    let level = experience.upgrades.len() + 2;
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            LevelUpScreen,
+            StateScoped(GameState::LevelUp),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(format!("Level {level}")),
+                TextFont {
+                    font_size: 33.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+            ));
+            parent.spawn((
+                Text::new("Choose an upgrade"),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.7, 0.7, 0.7)),
+            ));
+
+            for (index, upgrade) in choices.iter().enumerate() {
+                parent
+                    .spawn((
+                        Button,
+                        Node {
+                            width: Val::Px(360.0),
+                            margin: UiRect::all(Val::Px(6.0)),
+                            padding: UiRect::all(Val::Px(10.0)),
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(ui::NORMAL_BUTTON),
+                        UpgradeButton(*upgrade),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(ui::button_text(
+                            format!("{}. {}", index + 1, upgrade.label()),
+                            26.0,
+                        ));
+                        parent.spawn((
+                            Text::new(upgrade.description()),
+                            TextFont {
+                                font_size: 16.0,
+                                ..default()
+                            },
+                            TextColor(Color::srgb(0.7, 0.7, 0.7)),
+                        ));
+                    });
+            }
+        });
+
+    commands.insert_resource(UpgradeChoices(choices));
+}
+
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+fn choose_upgrade(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    choices: Res<UpgradeChoices>,
+    mut experience: ResMut<Experience>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    interaction_query: Query<(&Interaction, &UpgradeButton), Changed<Interaction>>,
+    mut player_query: Query<
+        (Entity, &mut Health, &mut MovementSpeed, &mut MeleeAttack),
+        With<Player>,
+    >,
+    mut weapon_query: Query<(&mut Weapon, &Parent)>,
+    screen_query: Query<Entity, With<LevelUpScreen>>,
+) {
+    let mut chosen = CHOICE_KEYS
+        .iter()
+        .zip(&choices.0)
+        .find(|(key, _)| input.just_pressed(**key))
+        .map(|(_, upgrade)| *upgrade);
+
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Pressed {
+            chosen = Some(button.0);
+        }
+    }
+
+    let Some(upgrade) = chosen else {
+        return;
+    };
+    if let Ok((player, mut health, mut speed, mut attack)) = player_query.get_single_mut() {
+        upgrade.apply(&mut health, &mut speed, &mut attack);
+        for (mut weapon, parent) in weapon_query.iter_mut() {
+            if parent.get() == player {
+                upgrade.apply_to_weapon(&mut weapon);
+            }
+        }
+    }
+    experience.upgrades.push(upgrade);
+
+    // Several levels may have been gained at once, offer the next pick
+    // right away instead of going through a frame of play
+    for screen in screen_query.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+    if experience.pending_levels() > 0 {
+        spawn_level_up_screen(&mut commands, &experience);
+    } else {
+        next_game_state.set(GameState::Ongoing);
+    }
+}
+
+fn forget_upgrade_choices(mut commands: Commands) {
+    commands.remove_resource::<UpgradeChoices>();
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            StateScoped(AppState::InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+                ExperienceText,
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(200.0),
+                        height: Val::Px(6.0),
+                        margin: UiRect::top(Val::Px(4.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(ORB_COLOR),
+                        ExperienceBar,
+                    ));
+                });
+        });
+}
+
+fn update_hud(
+    experience: Res<Experience>,
+    mut text_query: Query<&mut Text, With<ExperienceText>>,
+    mut bar_query: Query<&mut Node, With<ExperienceBar>>,
+) {
+    if !experience.is_changed() {
+        return;
+    }
+
+    let needed = experience.xp_to_next_level();
+    for mut text in text_query.iter_mut() {
+        text.0 = format!(
+            "Level {}  {:.0}/{:.0} XP",
+            experience.level, experience.xp, needed
+        );
+    }
+    for mut bar in bar_query.iter_mut() {
+        bar.width = Val::Percent(100.0 * (experience.xp / needed).min(1.0));
+    }
+}
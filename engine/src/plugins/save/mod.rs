@@ -0,0 +1,523 @@
+use std::{
+    cmp::Reverse,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{AssetServer, Assets, LoadState},
+    input::ButtonInput,
+    log::{error, info},
+    prelude::{
+        in_state, resource_exists, Commands, Condition, Entity, IntoSystemConfigs, KeyCode,
+        OnEnter, Parent, Query, Res, ResMut, Resource, Transform, With,
+    },
+    time::{Time, Timer, TimerMode},
+    window::{PrimaryWindow, Window},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::{
+    health::Health,
+    state::{AppState, GameState},
+};
+
+use super::{
+    enemy::{archetype::EnemyArchetype, spawn_enemy, Enemy, EnemyKind},
+    map::{Map, MapGenConfig},
+    player::{MeleeAttack, MovementSpeed, Player},
+    progression::{Experience, Upgrade},
+    stats::{reset_run_stats, RunStats},
+    spawner::Waves,
+    weapon::{equip_starting_weapons, Weapon},
+};
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Autosave(Timer::from_seconds(
+                AUTOSAVE_INTERVAL_SECS,
+                TimerMode::Repeating,
+            )))
+            .add_systems(OnEnter(AppState::InGame), start_session.after(reset_run_stats))
+            .add_systems(
+                Update,
+                restore_entities
+                    // The upgrades are replayed on the starting weapons too
+                    .after(equip_starting_weapons)
+                    .run_if(in_state(AppState::InGame).and(resource_exists::<PendingLoad>)),
+            )
+            .add_systems(
+                Update,
+                save_game.run_if(in_state(GameState::Ongoing)),
+            );
+    }
+}
+
+/// Version written into new saves. Bump it whenever `SaveData` changes and add
+/// the matching step to `MIGRATIONS`.
+pub const SAVE_VERSION: u32 = 5;
+
+/// `MIGRATIONS[n]` upgrades a save of version `n + 1` to version `n + 2`, so
+/// old saves are brought up to date one step at a time before being parsed.
+/// Migrations write the literal values the game used back then, never the
+/// current defaults, which keep changing.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    migrate_v1_map_config,
+    migrate_v2_biomes,
+    migrate_v3_experience,
+    migrate_v4_run_stats,
+];
+
+/// Version 1 only stored the seed and chunk size of the map, the other
+/// generation parameters were hard-coded to the values below.
+fn migrate_v1_map_config(save: &mut Value) {
+    let map = json!({
+        "seed": save["map"]["seed"],
+        "chunk_size": save["map"]["chunk_size"],
+        "world_size": null,
+        "octaves": 1,
+        "frequency": 1.0 / 200.0,
+        "water_threshold": -0.2,
+        "dirt_threshold": 0.2,
+    });
+    save["map"] = map;
+}
+
+/// Version 2 had no biomes, land was split into grass and dirt by elevation
+/// alone. Its worlds can't be reproduced, so the biome parameters are the ones
+/// biomes were introduced with and only the shared fields are kept.
+fn migrate_v2_biomes(save: &mut Value) {
+    let old = &save["map"];
+    let map = json!({
+        "seed": old["seed"],
+        "chunk_size": old["chunk_size"],
+        "world_size": old["world_size"],
+        "octaves": old["octaves"],
+        "frequency": old["frequency"],
+        "climate_frequency": 1.0 / 400.0,
+        "deep_water_threshold": -0.6,
+        "water_threshold": -0.45,
+        "sand_threshold": -0.35,
+        "stone_threshold": 0.6,
+        "snow_threshold": 0.85,
+        "forest_threshold": 0.3,
+        "dry_threshold": -0.4,
+        "cold_threshold": -0.75,
+    });
+    save["map"] = map;
+}
+
+/// Version 3 predates experience, its player starts over at level 1.
+fn migrate_v3_experience(save: &mut Value) {
+    save["player"]["level"] = Value::from(1);
+    save["player"]["xp"] = Value::from(0.0);
+    save["player"]["upgrades"] = Value::Array(Vec::new());
+}
+
+/// Version 4 only kept the play time of the run, its other statistics start
+/// from zero.
+fn migrate_v4_run_stats(save: &mut Value) {
+    let elapsed_secs = save
+        .as_object_mut()
+        .and_then(|save| save.remove("elapsed_secs"))
+        .unwrap_or(Value::from(0.0));
+    save["stats"] = json!({
+        "time_secs": elapsed_secs,
+        "waves_reached": save["wave"],
+    });
+}
+
+/// Directory the save files are written to
+const SAVE_DIRECTORY: &str = "saves";
+
+const QUICKSAVE_NAME: &str = "quicksave";
+const AUTOSAVE_NAME: &str = "autosave";
+const AUTOSAVE_INTERVAL_SECS: f32 = 60.0;
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub map: MapGenConfig,
+    pub stats: RunStats,
+    pub wave: u32,
+    pub secs_until_next_wave: f32,
+    pub player: PlayerSave,
+    pub enemies: Vec<EnemySave>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave {
+    pub position: [f32; 2],
+    pub health: f32,
+    pub max_health: f32,
+    pub level: u32,
+    pub xp: f32,
+    /// Upgrades picked so far, replayed onto the player's base stats
+    pub upgrades: Vec<Upgrade>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EnemySave {
+    /// Asset path of the enemy's archetype file
+    pub archetype: String,
+    pub position: [f32; 2],
+    pub health: f32,
+    pub max_health: f32,
+    pub difficulty: f32,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] io::Error),
+    #[error("could not parse save file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("save file has no version")]
+    MissingVersion,
+    #[error("save file version {0} is newer than this game supports")]
+    UnsupportedVersion(u64),
+    #[error("save file version {0} is not a valid version")]
+    InvalidVersion(u64),
+}
+
+/// A save file found on disk.
+pub struct SaveSlot {
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: SystemTime,
+}
+
+/// A save waiting to be applied once the game has been entered.
+#[derive(Resource)]
+pub struct PendingLoad(pub SaveData);
+
+#[derive(Resource)]
+struct Autosave(Timer);
+
+/// Lists the save files, most recently written first.
+pub fn list_saves() -> Vec<SaveSlot> {
+    let Ok(entries) = fs::read_dir(SAVE_DIRECTORY) else {
+        return Vec::new();
+    };
+
+    let mut saves: Vec<SaveSlot> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            Some(SaveSlot {
+                name,
+                path,
+                modified,
+            })
+        })
+        .collect();
+
+    saves.sort_by_key(|save| Reverse(save.modified));
+    saves
+}
+
+/// Reads a save file, migrating it from older versions if needed.
+pub fn read_save(path: &Path) -> Result<SaveData, SaveError> {
+    let mut value: Value = serde_json::from_slice(&fs::read(path)?)?;
+
+    let mut version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(SaveError::MissingVersion)?;
+    if version > SAVE_VERSION as u64 {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    // Versions start at 1, there is no migration from anything older
+    if version == 0 {
+        return Err(SaveError::InvalidVersion(version));
+    }
+
+    while version < SAVE_VERSION as u64 {
+        MIGRATIONS[version as usize - 1](&mut value);
+        version += 1;
+        value["version"] = Value::from(version);
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+fn write_save(name: &str, save: &SaveData) -> Result<PathBuf, SaveError> {
+    fs::create_dir_all(SAVE_DIRECTORY)?;
+    let path = PathBuf::from(SAVE_DIRECTORY).join(format!("{name}.json"));
+    fs::write(&path, serde_json::to_vec_pretty(save)?)?;
+    Ok(path)
+}
+
+fn start_session(
+    mut commands: Commands,
+    pending_load: Option<Res<PendingLoad>>,
+    mut run_stats: ResMut<RunStats>,
+    mut autosave: ResMut<Autosave>,
+) {
+    autosave.0.reset();
+
+    let Some(pending_load) = pending_load else {
+        return;
+    };
+
+    // The wave progress has to be in place before the first frame, the
+    // entities are restored once they exist. The map plugin builds the saved world.
+    let save = &pending_load.0;
+    *run_stats = save.stats.clone();
+    commands.insert_resource(Waves::resume(save.wave, save.secs_until_next_wave));
+}
+
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+fn restore_entities(
+    mut commands: Commands,
+    pending_load: Res<PendingLoad>,
+    asset_server: Res<AssetServer>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    mut experience: ResMut<Experience>,
+    mut player_query: Query<
+        (Entity, &mut Transform, &mut Health, &mut MovementSpeed, &mut MeleeAttack),
+        With<Player>,
+    >,
+    mut weapon_query: Query<(&mut Weapon, &Parent)>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let (Ok(player), Ok(window)) = (player_query.get_single_mut(), window_query.get_single())
+    else {
+        return;
+    };
+    let (player, mut player_transform, mut player_health, mut speed, mut attack) = player;
+
+    let save = &pending_load.0;
+    let enemies: Vec<_> = save
+        .enemies
+        .iter()
+        .map(|enemy| (enemy, asset_server.load::<EnemyArchetype>(&enemy.archetype)))
+        .collect();
+    // Wait for the archetypes, they are normally loaded long before a save is.
+    // Enemies whose archetype file is gone are skipped below.
+    if enemies.iter().any(|(_, handle)| {
+        matches!(
+            asset_server.load_state(handle),
+            LoadState::NotLoaded | LoadState::Loading
+        )
+    }) {
+        return;
+    }
+
+    let [x, y] = save.player.position;
+    player_transform.translation.x = x;
+    player_transform.translation.y = y;
+    for upgrade in &save.player.upgrades {
+        upgrade.apply(&mut player_health, &mut speed, &mut attack);
+        for (mut weapon, parent) in weapon_query.iter_mut() {
+            if parent.get() == player {
+                upgrade.apply_to_weapon(&mut weapon);
+            }
+        }
+    }
+    // The saved health already includes the upgrades
+    *player_health = Health {
+        current: save.player.health,
+        max: save.player.max_health,
+    };
+    *experience = Experience {
+        level: save.player.level,
+        xp: save.player.xp,
+        upgrades: save.player.upgrades.clone(),
+    };
+
+    for (enemy, handle) in enemies {
+        let Some(archetype) = archetypes.get(&handle) else {
+            continue;
+        };
+        let entity = spawn_enemy(
+            &mut commands,
+            handle.clone(),
+            archetype,
+            window,
+            enemy.position.into(),
+            enemy.difficulty,
+        );
+        commands.entity(entity).insert(Health {
+            current: enemy.health,
+            max: enemy.max_health,
+        });
+    }
+
+    commands.remove_resource::<PendingLoad>();
+    info!("Restored saved game");
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_game(
+    time: Res<Time>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut autosave: ResMut<Autosave>,
+    map: Res<Map>,
+    run_stats: Res<RunStats>,
+    waves: Res<Waves>,
+    experience: Res<Experience>,
+    player_query: Query<(&Transform, &Health), With<Player>>,
+    enemy_query: Query<(&Transform, &Health, &EnemyKind), With<Enemy>>,
+) {
+    autosave.0.tick(time.delta());
+    let name = if input.just_pressed(KeyCode::F5) {
+        QUICKSAVE_NAME
+    } else if autosave.0.just_finished() {
+        AUTOSAVE_NAME
+    } else {
+        return;
+    };
+
+    let Ok((player_transform, player_health)) = player_query.get_single() else {
+        return;
+    };
+
+    let save = SaveData {
+        version: SAVE_VERSION,
+        map: map.config.clone(),
+        stats: run_stats.clone(),
+        wave: waves.current,
+        secs_until_next_wave: waves.secs_until_next(),
+        player: PlayerSave {
+            position: player_transform.translation.truncate().to_array(),
+            health: player_health.current,
+            max_health: player_health.max,
+            level: experience.level,
+            xp: experience.xp,
+            upgrades: experience.upgrades.clone(),
+        },
+        enemies: enemy_query
+            .iter()
+            .filter_map(|(transform, health, kind)| {
+                Some(EnemySave {
+                    archetype: kind.archetype.path()?.to_string(),
+                    position: transform.translation.truncate().to_array(),
+                    health: health.current,
+                    max_health: health.max,
+                    difficulty: kind.difficulty,
+                })
+            })
+            .collect(),
+    };
+
+    match write_save(name, &save) {
+        Ok(path) => info!("Game saved to {}", path.display()),
+        Err(err) => error!("Could not save the game: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_fixture(name: &str) -> Result<SaveData, SaveError> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/plugins/save/fixtures")
+            .join(format!("{name}.json"));
+        read_save(&path)
+    }
+
+    /// Biome parameters the game shipped with when biomes were introduced in
+    /// version 3
+    fn v3_biomes(map: MapGenConfig) -> MapGenConfig {
+        MapGenConfig {
+            climate_frequency: 1.0 / 400.0,
+            deep_water_threshold: -0.6,
+            water_threshold: -0.45,
+            sand_threshold: -0.35,
+            stone_threshold: 0.6,
+            snow_threshold: 0.85,
+            forest_threshold: 0.3,
+            dry_threshold: -0.4,
+            cold_threshold: -0.75,
+            ..map
+        }
+    }
+
+    #[test]
+    fn v1_save_keeps_the_map_it_was_made_with() {
+        let save = read_fixture("v1").unwrap();
+
+        assert_eq!(save.version, SAVE_VERSION);
+        let expected = v3_biomes(MapGenConfig {
+            seed: 42,
+            chunk_size: 16,
+            world_size: None,
+            octaves: 1,
+            frequency: 1.0 / 200.0,
+            ..save.map.clone()
+        });
+        assert_eq!(save.map, expected);
+        assert_eq!(save.stats.time_secs, 90.5);
+        assert_eq!(save.stats.waves_reached, 3);
+        assert_eq!(save.player.level, 1);
+        assert_eq!(save.player.xp, 0.0);
+        assert!(save.player.upgrades.is_empty());
+        assert_eq!(save.enemies.len(), 1);
+        assert_eq!(save.enemies[0].archetype, "enemies/grunt.enemy.json");
+    }
+
+    #[test]
+    fn v2_save_keeps_its_elevation_settings() {
+        let save = read_fixture("v2").unwrap();
+
+        let expected = v3_biomes(MapGenConfig {
+            seed: 7,
+            chunk_size: 32,
+            world_size: Some(500),
+            octaves: 3,
+            frequency: 0.01,
+            ..save.map.clone()
+        });
+        assert_eq!(save.map, expected);
+        assert_eq!(save.stats.time_secs, 45.0);
+    }
+
+    #[test]
+    fn v3_save_keeps_its_biomes() {
+        let save = read_fixture("v3").unwrap();
+
+        assert_eq!(save.map.octaves, 5);
+        assert_eq!(save.map.climate_frequency, 0.002);
+        assert_eq!(save.map.cold_threshold, -0.7);
+        assert_eq!(save.player.level, 1);
+        assert_eq!(save.player.health, 55.0);
+        assert_eq!(save.stats.waves_reached, 4);
+    }
+
+    #[test]
+    fn v4_save_keeps_its_experience() {
+        let save = read_fixture("v4").unwrap();
+
+        assert_eq!(save.player.level, 3);
+        assert_eq!(save.player.xp, 2.5);
+        assert_eq!(save.player.upgrades, [Upgrade::MaxHealth, Upgrade::Magnet]);
+        assert_eq!(save.stats.time_secs, 300.0);
+        assert_eq!(save.stats.waves_reached, 6);
+        assert_eq!(save.stats.enemies_killed, 0);
+    }
+
+    #[test]
+    fn version_0_is_rejected() {
+        assert!(matches!(read_fixture("v0"), Err(SaveError::InvalidVersion(0))));
+    }
+
+    #[test]
+    fn future_version_is_rejected() {
+        assert!(matches!(
+            read_fixture("future"),
+            Err(SaveError::UnsupportedVersion(999))
+        ));
+    }
+}
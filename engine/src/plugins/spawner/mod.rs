@@ -0,0 +1,183 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{AssetServer, Assets, LoadedFolder},
+    math::Vec2,
+    prelude::{
+        in_state, Camera, Commands, IntoSystemConfigs, OnExit, Query, Res, ResMut, Resource,
+        Transform, With,
+    },
+    time::{Time, Timer, TimerMode},
+    window::{PrimaryWindow, Window},
+};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::{
+    grid::{snap_to_tile, TILE_SIZE},
+    state::{AppState, GameState},
+};
+
+use super::{
+    enemy::{archetype::EnemyArchetype, spawn_enemy, EnemyArchetypes},
+    map::Map,
+};
+
+pub struct SpawnerPlugin;
+
+impl Plugin for SpawnerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WaveConfig::default())
+            .insert_resource(Waves::default())
+            .add_systems(OnExit(AppState::InGame), reset_waves)
+            .add_systems(Update, spawn_waves.run_if(in_state(GameState::Ongoing)));
+    }
+}
+
+/// Tuning for how waves are timed and how quickly they ramp up.
+#[derive(Resource)]
+pub struct WaveConfig {
+    /// Seconds between the start of two waves
+    pub interval_secs: f32,
+    /// Number of enemies in the first wave
+    pub base_size: u32,
+    /// Extra enemies added to every following wave
+    pub size_growth: u32,
+    /// Difficulty added to every following wave, the first wave is 1.0
+    pub difficulty_growth: f32,
+    /// Distance in pixels beyond the edge of the camera view where enemies appear
+    pub spawn_margin: f32,
+}
+
+impl Default for WaveConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 20.0,
+            base_size: 3,
+            size_growth: 2,
+            difficulty_growth: 0.15,
+            spawn_margin: 2.0 * TILE_SIZE,
+        }
+    }
+}
+
+impl WaveConfig {
+    pub fn wave_size(&self, wave: u32) -> u32 {
+        self.base_size + self.size_growth * wave.saturating_sub(1)
+    }
+
+    pub fn difficulty(&self, wave: u32) -> f32 {
+        1.0 + self.difficulty_growth * wave.saturating_sub(1) as f32
+    }
+}
+
+/// Wave progress of the current run.
+#[derive(Resource)]
+pub struct Waves {
+    /// Number of the last wave that was spawned, 0 before the first one
+    pub current: u32,
+    timer: Timer,
+}
+
+impl Waves {
+    /// Seconds left until the next wave starts
+    pub fn secs_until_next(&self) -> f32 {
+        self.timer.remaining_secs()
+    }
+
+    /// Continues a run that already reached wave `current`.
+    pub fn resume(current: u32, secs_until_next: f32) -> Self {
+        Self {
+            current,
+            timer: Timer::from_seconds(secs_until_next.max(0.0), TimerMode::Once),
+        }
+    }
+}
+
+impl Default for Waves {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            timer: Timer::from_seconds(0.0, TimerMode::Once),
+        }
+    }
+}
+
+/// How many random positions are tried per enemy before giving up on it
+const SPAWN_ATTEMPTS: usize = 16;
+
+fn reset_waves(mut waves: ResMut<Waves>) {
+    // The first wave of the next run starts right away
+    *waves = Waves::default();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_waves(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<WaveConfig>,
+    map: Res<Map>,
+    asset_server: Res<AssetServer>,
+    enemy_archetypes: Res<EnemyArchetypes>,
+    folders: Res<Assets<LoadedFolder>>,
+    archetype_assets: Res<Assets<EnemyArchetype>>,
+    mut waves: ResMut<Waves>,
+    camera_query: Query<&Transform, With<Camera>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    waves.timer.tick(time.delta());
+    if !waves.timer.finished() {
+        return;
+    }
+
+    // Hold the wave back until the archetype files have loaded
+    let (Ok(camera_transform), Ok(window), Some(archetypes)) = (
+        camera_query.get_single(),
+        window_query.get_single(),
+        enemy_archetypes.loaded(&asset_server, &folders, &archetype_assets),
+    ) else {
+        return;
+    };
+
+    waves.current += 1;
+    waves.timer = Timer::from_seconds(config.interval_secs, TimerMode::Once);
+
+    let wave = waves.current;
+    let difficulty = config.difficulty(wave);
+
+    let available: Vec<_> = archetypes
+        .into_iter()
+        .filter(|(_, archetype)| archetype.min_wave <= wave)
+        .collect();
+    let Ok(weights) = WeightedIndex::new(available.iter().map(|(_, archetype)| archetype.spawn_weight))
+    else {
+        return;
+    };
+
+    // Spawn on a ring just outside the visible area around the camera
+    let camera_position = camera_transform.translation.truncate();
+    let spawn_distance = window.size().length() / 2.0 + config.spawn_margin;
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..config.wave_size(wave) {
+        let position = (0..SPAWN_ATTEMPTS)
+            .map(|_| {
+                let angle = rng.gen_range(0.0..TAU);
+                let distance = spawn_distance + rng.gen_range(0.0..config.spawn_margin);
+                snap_to_tile(camera_position + Vec2::from_angle(angle) * distance)
+            })
+            .find(|position| map.tile_at(*position).is_walkable());
+
+        if let Some(position) = position {
+            let (handle, archetype) = &available[weights.sample(&mut rng)];
+            spawn_enemy(
+                &mut commands,
+                handle.clone(),
+                archetype,
+                window,
+                position,
+                difficulty,
+            );
+        }
+    }
+}
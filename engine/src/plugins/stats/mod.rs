@@ -0,0 +1,172 @@
+use std::{fs, io, path::Path};
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    log::error,
+    prelude::{
+        in_state, EventReader, IntoSystemConfigs, OnEnter, Query, Res, ResMut, Resource, With,
+    },
+    time::Time,
+};
+use bevy_rapier2d::prelude::Velocity;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    events::{DamageDealtEvent, DeathEvent},
+    state::{AppState, GameState},
+};
+
+use super::{damage::DamageSet, enemy::Enemy, player::Player, spawner::Waves};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .init_resource::<HighScores>()
+            .add_systems(Startup, load_high_scores)
+            .add_systems(OnEnter(AppState::InGame), reset_run_stats)
+            .add_systems(
+                Update,
+                (
+                    track_time,
+                    track_damage.after(DamageSet::Apply),
+                    // Killed enemies are despawned at the end of `DamageSet::Death`
+                    track_kills.in_set(DamageSet::Death),
+                )
+                    .run_if(in_state(GameState::Ongoing)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), record_high_score);
+    }
+}
+
+/// What happened during the current run, shown when it ends and saved along
+/// with it.
+#[derive(Resource, Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct RunStats {
+    /// Seconds survived
+    pub time_secs: f32,
+    pub enemies_killed: u32,
+    /// Damage the player dealt, after armor and resistances
+    pub damage_dealt: f32,
+    /// Damage the player took, after armor and resistances
+    pub damage_taken: f32,
+    /// Pixels the player moved
+    pub distance: f32,
+    pub waves_reached: u32,
+}
+
+/// The best runs played on this machine, best first, and where the last
+/// finished run placed among them.
+#[derive(Resource, Default)]
+pub struct HighScores {
+    pub runs: Vec<RunStats>,
+    /// Position of the run that just ended, `None` when it didn't make the list
+    pub last_rank: Option<usize>,
+}
+
+/// File the high scores are kept in, outside of the save directory so it
+/// isn't listed as a save
+const HIGH_SCORES_PATH: &str = "highscores.json";
+
+/// How many runs the high score list keeps
+const MAX_HIGH_SCORES: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum HighScoreError {
+    #[error("could not access high scores: {0}")]
+    Io(#[from] io::Error),
+    #[error("could not parse high scores: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl HighScores {
+    /// Adds a finished run, keeping the list sorted by time survived, then by
+    /// enemies killed.
+    fn record(&mut self, run: RunStats) {
+        let rank = self
+            .runs
+            .iter()
+            .position(|best| {
+                (run.time_secs, run.enemies_killed) > (best.time_secs, best.enemies_killed)
+            })
+            .unwrap_or(self.runs.len());
+        self.runs.insert(rank, run);
+        self.runs.truncate(MAX_HIGH_SCORES);
+        self.last_rank = (rank < MAX_HIGH_SCORES).then_some(rank);
+    }
+}
+
+fn read_high_scores(path: &Path) -> Result<Vec<RunStats>, HighScoreError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        // No run has been finished yet
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_high_scores(path: &Path, runs: &[RunStats]) -> Result<(), HighScoreError> {
+    fs::write(path, serde_json::to_vec_pretty(runs)?)?;
+    Ok(())
+}
+
+fn load_high_scores(mut high_scores: ResMut<HighScores>) {
+    match read_high_scores(Path::new(HIGH_SCORES_PATH)) {
+        Ok(runs) => high_scores.runs = runs,
+        Err(err) => error!("Could not load the high scores: {err}"),
+    }
+}
+
+pub fn reset_run_stats(mut run_stats: ResMut<RunStats>) {
+    *run_stats = RunStats::default();
+}
+
+fn track_time(
+    time: Res<Time>,
+    waves: Res<Waves>,
+    mut run_stats: ResMut<RunStats>,
+    player_query: Query<&Velocity, With<Player>>,
+) {
+    run_stats.time_secs += time.delta_secs();
+    run_stats.waves_reached = run_stats.waves_reached.max(waves.current);
+    // Rapier moves the player by its velocity, teleports aren't travelling
+    for velocity in player_query.iter() {
+        run_stats.distance += velocity.linvel.length() * time.delta_secs();
+    }
+}
+
+fn track_damage(
+    mut dealt_events: EventReader<DamageDealtEvent>,
+    mut run_stats: ResMut<RunStats>,
+    player_query: Query<(), With<Player>>,
+) {
+    for event in dealt_events.read() {
+        if player_query.contains(event.target) {
+            run_stats.damage_taken += event.amount;
+        } else if player_query.contains(event.source) {
+            run_stats.damage_dealt += event.amount;
+        }
+    }
+}
+
+fn track_kills(
+    mut death_events: EventReader<DeathEvent>,
+    mut run_stats: ResMut<RunStats>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for event in death_events.read() {
+        if enemy_query.contains(event.entity) {
+            run_stats.enemies_killed += 1;
+        }
+    }
+}
+
+pub fn record_high_score(run_stats: Res<RunStats>, mut high_scores: ResMut<HighScores>) {
+    high_scores.record(run_stats.clone());
+    if let Err(err) = write_high_scores(Path::new(HIGH_SCORES_PATH), &high_scores.runs) {
+        error!("Could not save the high scores: {err}");
+    }
+}
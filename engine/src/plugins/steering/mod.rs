@@ -0,0 +1,172 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    math::{IVec2, Vec2},
+    prelude::{
+        Entity, IntoSystemConfigs, Query, Res, ResMut, Resource, Transform, With, Without,
+    },
+    utils::HashMap,
+};
+use bevy_rapier2d::prelude::Velocity;
+
+use super::{
+    enemy::{Enemy, EnemySet, MovementSpeed},
+    hit_reaction::Staggered,
+};
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SteeringConfig::default())
+            .init_resource::<EnemySpatialHash>()
+            .add_systems(
+                Update,
+                (update_spatial_hash, steer_enemies)
+                    .chain()
+                    .in_set(EnemySet::Steer),
+            );
+    }
+}
+
+/// Weights of the flocking forces blended into each enemy's velocity.
+#[derive(Resource)]
+pub struct SteeringConfig {
+    /// Enemies closer than this many pixels influence each other
+    pub neighbour_radius: f32,
+    /// Push away from neighbours, stronger the closer they are
+    pub separation: f32,
+    /// Pull towards the average velocity of neighbours
+    pub alignment: f32,
+    /// Pull towards the centre of neighbours
+    pub cohesion: f32,
+}
+
+impl Default for SteeringConfig {
+    fn default() -> Self {
+        Self {
+            neighbour_radius: 48.0,
+            separation: 1.5,
+            alignment: 0.2,
+            cohesion: 0.1,
+        }
+    }
+}
+
+/// Enemy positions and velocities bucketed into square cells, so finding the
+/// neighbours of an enemy only looks at the few cells around it instead of
+/// every enemy.
+#[derive(Resource, Default)]
+pub struct EnemySpatialHash {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Neighbour>>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Neighbour {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+impl EnemySpatialHash {
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Empties the hash. Cells used last frame keep their allocations for the
+    /// next one, the others are dropped so the map doesn't grow with every
+    /// cell an enemy ever passed through.
+    fn clear(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.cells.retain(|_, neighbours| {
+            let used = !neighbours.is_empty();
+            neighbours.clear();
+            used
+        });
+    }
+
+    fn insert(&mut self, neighbour: Neighbour) {
+        let cell = self.cell(neighbour.position);
+        self.cells.entry(cell).or_default().push(neighbour);
+    }
+
+    /// Returns the enemies within `radius` pixels of `position`.
+    pub fn within(&self, position: Vec2, radius: f32) -> impl Iterator<Item = &Neighbour> {
+        let min = self.cell(position - radius);
+        let max = self.cell(position + radius);
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |neighbour| neighbour.position.distance_squared(position) <= radius * radius)
+    }
+}
+
+fn update_spatial_hash(
+    config: Res<SteeringConfig>,
+    mut spatial_hash: ResMut<EnemySpatialHash>,
+    enemy_query: Query<(Entity, &Transform, &Velocity), With<Enemy>>,
+) {
+    // Cells as big as the neighbour radius keep each lookup within 3x3 cells
+    spatial_hash.clear(config.neighbour_radius);
+    for (entity, transform, velocity) in enemy_query.iter() {
+        spatial_hash.insert(Neighbour {
+            entity,
+            position: transform.translation.truncate(),
+            velocity: velocity.linvel,
+        });
+    }
+}
+
+/// Blends separation, alignment and cohesion into the velocity each enemy
+/// picked for itself. Separation keeps a crowd from collapsing onto one
+/// point, so chasing enemies end up spread around the player.
+#[allow(clippy::type_complexity)]
+fn steer_enemies(
+    config: Res<SteeringConfig>,
+    spatial_hash: Res<EnemySpatialHash>,
+    mut enemy_query: Query<
+        (Entity, &Transform, &mut Velocity, &MovementSpeed),
+        (With<Enemy>, Without<Staggered>),
+    >,
+) {
+    let radius = config.neighbour_radius;
+
+    for (entity, transform, mut velocity, movement_speed) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let mut separation = Vec2::ZERO;
+        let mut velocity_sum = Vec2::ZERO;
+        let mut position_sum = Vec2::ZERO;
+        let mut count = 0;
+        for neighbour in spatial_hash.within(position, radius) {
+            if neighbour.entity == entity {
+                continue;
+            }
+            let offset = position - neighbour.position;
+            let distance = offset.length();
+            // Enemies on the exact same spot are pushed apart in a fixed direction
+            let away = if distance > f32::EPSILON {
+                offset / distance
+            } else {
+                Vec2::from_angle(entity.index() as f32)
+            };
+            separation += away * (1.0 - distance / radius);
+            velocity_sum += neighbour.velocity;
+            position_sum += neighbour.position;
+            count += 1;
+        }
+        if count == 0 {
+            continue;
+        }
+
+        let max_speed = movement_speed.0;
+        let alignment = velocity_sum / count as f32 - velocity.linvel;
+        let cohesion = (position_sum / count as f32 - position) / radius;
+        let steering = separation * config.separation * max_speed
+            + alignment * config.alignment
+            + cohesion * config.cohesion * max_speed;
+
+        velocity.linvel = (velocity.linvel + steering).clamp_length_max(max_speed);
+    }
+}
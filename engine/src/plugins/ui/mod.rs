@@ -0,0 +1,96 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    ecs::system::EntityCommands,
+    prelude::{
+        Bundle, BuildChildren, Button, ChildBuild, ChildBuilder, Component, DetectChangesMut, Has,
+        Query, Text, With,
+    },
+    text::{TextColor, TextFont},
+    ui::{AlignItems, BackgroundColor, Interaction, JustifyContent, Node, UiRect, Val},
+    utils::default,
+};
+
+/// Colours every button of the menus and overlays. Each screen reacts to its
+/// own buttons being pressed.
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, color_buttons);
+    }
+}
+
+pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
+pub const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
+
+pub const TEXT_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+
+/// The button keyboard and gamepad input acts on. It is highlighted like a
+/// hovered button, and while a button has the focus the mouse only highlights
+/// through moving the focus.
+#[derive(Component)]
+pub struct Focused;
+
+/// A button with its label centred, to which the screen adds its own action.
+pub fn button(width: f32, height: f32) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            margin: UiRect::all(Val::Px(6.0)),
+            // horizontally center child text
+            justify_content: JustifyContent::Center,
+            // vertically center child text
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+    )
+}
+
+pub fn button_text(label: impl Into<String>, font_size: f32) -> impl Bundle {
+    (
+        Text::new(label),
+        TextFont {
+            font_size,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+    )
+}
+
+/// Spawns a labelled button carrying `action`, the component the screen's
+/// own system looks for when it is pressed.
+pub fn spawn_button<'a>(
+    parent: &'a mut ChildBuilder,
+    label: &str,
+    action: impl Bundle,
+    width: f32,
+    height: f32,
+    font_size: f32,
+) -> EntityCommands<'a> {
+    let mut button = parent.spawn((button(width, height), action));
+    button.with_children(|parent| {
+        parent.spawn(button_text(label, font_size));
+    });
+    button
+}
+
+fn color_buttons(
+    mut button_query: Query<(&Interaction, Has<Focused>, &mut BackgroundColor), With<Button>>,
+    focused_query: Query<(), With<Focused>>,
+) {
+    let any_focused = !focused_query.is_empty();
+    for (interaction, focused, mut color) in button_query.iter_mut() {
+        let target = match interaction {
+            Interaction::Pressed => PRESSED_BUTTON,
+            _ if focused => HOVERED_BUTTON,
+            Interaction::Hovered if !any_focused => HOVERED_BUTTON,
+            _ => NORMAL_BUTTON,
+        };
+        color.set_if_neq(BackgroundColor(target));
+    }
+}
@@ -0,0 +1,330 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    color::Color,
+    math::Vec2,
+    prelude::{
+        in_state, Added, BuildChildren, ChildBuild, Commands, Component, DespawnRecursiveExt,
+        Entity, EventReader, EventWriter, IntoSystemConfigs, Name, Parent, Query, Res, StateScoped,
+        Transform, With, Without,
+    },
+    sprite::Sprite,
+    time::{Time, Timer, TimerMode},
+    utils::default,
+};
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, CollisionEvent, RigidBody, Sensor, Velocity};
+
+use crate::{
+    events::{DamageEvent, DamageType},
+    state::{AppState, GameState},
+};
+
+use super::{
+    damage::DamageSet,
+    enemy::{Enemy, EnemySet},
+    player::{Facing, Player},
+    steering::EnemySpatialHash,
+};
+
+pub struct WeaponPlugin;
+
+impl Plugin for WeaponPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                equip_starting_weapons,
+                // Aims at the enemy positions gathered during steering
+                fire_weapons.after(EnemySet::Steer),
+                (orbit_projectiles, expire_projectiles),
+                projectile_hits.in_set(DamageSet::Emit),
+            )
+                .run_if(in_state(GameState::Ongoing)),
+        );
+    }
+}
+
+/// A weapon firing volleys of projectiles on its own whenever its cooldown is
+/// up. Weapons are children of the entity wielding them, which can hold any
+/// number of them.
+#[derive(Component, Clone)]
+pub struct Weapon {
+    pub damage: f32,
+    pub damage_type: DamageType,
+    pub cooldown: Timer,
+    pub targeting: Targeting,
+    /// Projectiles fired per volley, fanned out or spread around the orbit
+    pub count: u32,
+    pub projectile: ProjectileStats,
+}
+
+/// Where a weapon sends its projectiles.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Targeting {
+    /// Fires at the closest enemy within `range` pixels, holding fire while
+    /// there is none
+    Nearest { range: f32 },
+    /// Fires the way the wielder last moved
+    Movement,
+    /// Projectiles circle the wielder `radius` pixels away from it
+    Orbit { radius: f32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ProjectileStats {
+    /// Pixels per second, along the circle for orbiting projectiles
+    pub speed: f32,
+    /// Enemies a projectile passes through before being destroyed
+    pub pierce: u32,
+    pub lifetime_secs: f32,
+    pub radius: f32,
+    pub color: Color,
+}
+
+/// A projectile in flight. It hits each enemy at most once.
+#[derive(Component)]
+pub struct Projectile {
+    /// Entity credited with the damage
+    pub owner: Entity,
+    pub damage: f32,
+    pub damage_type: DamageType,
+    /// Enemies it can still hit before being destroyed
+    hits_left: u32,
+    hit: Vec<Entity>,
+    lifetime: Timer,
+}
+
+/// Keeps a projectile circling its owner instead of flying straight.
+#[derive(Component)]
+struct Orbit {
+    angle: f32,
+    radius: f32,
+    /// Radians per second, so the projectile keeps the speed of its weapon
+    /// whatever the radius
+    angular_speed: f32,
+}
+
+/// Angle in radians between two projectiles of a fanned out volley
+const FAN_ANGLE: f32 = 0.2;
+
+fn magic_bolt() -> Weapon {
+    Weapon {
+        damage: 12.0,
+        damage_type: DamageType::Fire,
+        cooldown: Timer::from_seconds(1.0, TimerMode::Once),
+        targeting: Targeting::Nearest { range: 420.0 },
+        count: 1,
+        projectile: ProjectileStats {
+            speed: 480.0,
+            pierce: 0,
+            lifetime_secs: 1.5,
+            radius: 5.0,
+            color: Color::srgb(1.0, 0.55, 0.2),
+        },
+    }
+}
+
+fn throwing_knives() -> Weapon {
+    Weapon {
+        damage: 6.0,
+        damage_type: DamageType::Physical,
+        cooldown: Timer::from_seconds(0.8, TimerMode::Once),
+        targeting: Targeting::Movement,
+        count: 3,
+        projectile: ProjectileStats {
+            speed: 620.0,
+            pierce: 1,
+            lifetime_secs: 0.6,
+            radius: 4.0,
+            color: Color::srgb(0.8, 0.8, 0.85),
+        },
+    }
+}
+
+fn frost_orbs() -> Weapon {
+    Weapon {
+        damage: 8.0,
+        damage_type: DamageType::Frost,
+        cooldown: Timer::from_seconds(4.0, TimerMode::Once),
+        targeting: Targeting::Orbit { radius: 72.0 },
+        count: 2,
+        projectile: ProjectileStats {
+            speed: 260.0,
+            pierce: 8,
+            lifetime_secs: 2.5,
+            radius: 8.0,
+            color: Color::srgb(0.5, 0.8, 1.0),
+        },
+    }
+}
+
+/// Gives a fresh player its starting weapons.
+pub fn equip_starting_weapons(mut commands: Commands, player_query: Query<Entity, Added<Player>>) {
+    for player in player_query.iter() {
+        commands.entity(player).with_children(|parent| {
+            parent.spawn((Name::new("Magic bolt"), magic_bolt()));
+            parent.spawn((Name::new("Throwing knives"), throwing_knives()));
+            parent.spawn((Name::new("Frost orbs"), frost_orbs()));
+        });
+    }
+}
+
+fn fire_weapons(
+    mut commands: Commands,
+    time: Res<Time>,
+    spatial_hash: Res<EnemySpatialHash>,
+    wielder_query: Query<(&Transform, Option<&Facing>)>,
+    mut weapon_query: Query<(&mut Weapon, &Parent)>,
+) {
+    for (mut weapon, parent) in weapon_query.iter_mut() {
+        weapon.cooldown.tick(time.delta());
+        if !weapon.cooldown.finished() {
+            continue;
+        }
+        let Ok((wielder_transform, facing)) = wielder_query.get(parent.get()) else {
+            continue;
+        };
+        let origin = wielder_transform.translation.truncate();
+
+        let aim = match weapon.targeting {
+            Targeting::Nearest { range } => spatial_hash
+                .within(origin, range)
+                .map(|enemy| enemy.position - origin)
+                .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared())),
+            Targeting::Movement => Some(facing.map_or(Vec2::X, |facing| facing.0)),
+            Targeting::Orbit { .. } => Some(Vec2::X),
+        };
+        // Nothing to shoot at, fire as soon as something comes in range
+        let Some(aim) = aim.and_then(|aim| aim.try_normalize()) else {
+            continue;
+        };
+        weapon.cooldown.reset();
+
+        for index in 0..weapon.count {
+            let projectile = Projectile {
+                owner: parent.get(),
+                damage: weapon.damage,
+                damage_type: weapon.damage_type,
+                hits_left: weapon.projectile.pierce + 1,
+                hit: Vec::new(),
+                lifetime: Timer::from_seconds(weapon.projectile.lifetime_secs, TimerMode::Once),
+            };
+            let stats = weapon.projectile;
+            let sprite = Sprite {
+                color: stats.color,
+                custom_size: Some(Vec2::splat(stats.radius * 2.0)),
+                ..default()
+            };
+            let bundle = (
+                sprite,
+                projectile,
+                StateScoped(AppState::InGame),
+                Collider::ball(stats.radius),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+            );
+
+            match weapon.targeting {
+                Targeting::Orbit { radius } => {
+                    let angle = std::f32::consts::TAU * index as f32 / weapon.count as f32;
+                    commands.spawn((
+                        bundle,
+                        Transform::from_translation(
+                            (origin + Vec2::from_angle(angle) * radius).extend(1.5),
+                        ),
+                        RigidBody::KinematicPositionBased,
+                        Orbit {
+                            angle,
+                            radius,
+                            angular_speed: stats.speed / radius,
+                        },
+                    ));
+                }
+                Targeting::Nearest { .. } | Targeting::Movement => {
+                    // Fan the volley out evenly around the aim
+                    let offset = (index as f32 - (weapon.count - 1) as f32 / 2.0) * FAN_ANGLE;
+                    let direction = Vec2::from_angle(offset).rotate(aim);
+                    commands.spawn((
+                        bundle,
+                        Transform::from_translation(origin.extend(1.5)),
+                        RigidBody::KinematicVelocityBased,
+                        Velocity::linear(direction * stats.speed),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Moves orbiting projectiles along their circle around their owner.
+fn orbit_projectiles(
+    time: Res<Time>,
+    owner_query: Query<&Transform, Without<Projectile>>,
+    mut projectile_query: Query<(&mut Transform, &mut Orbit, &Projectile)>,
+) {
+    for (mut transform, mut orbit, projectile) in projectile_query.iter_mut() {
+        let Ok(owner_transform) = owner_query.get(projectile.owner) else {
+            continue;
+        };
+        orbit.angle += orbit.angular_speed * time.delta_secs();
+        let position =
+            owner_transform.translation.truncate() + Vec2::from_angle(orbit.angle) * orbit.radius;
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectile_query: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectile_query.iter_mut() {
+        projectile.lifetime.tick(time.delta());
+        if projectile.lifetime.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Damages the enemies projectiles run into and destroys the projectiles that
+/// ran out of pierce.
+fn projectile_hits(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut projectile_query: Query<(&mut Projectile, &Transform)>,
+    enemy_query: Query<(), With<Enemy>>,
+) {
+    for event in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = event else {
+            continue;
+        };
+        let (projectile_entity, target) = if projectile_query.contains(*first) {
+            (*first, *second)
+        } else {
+            (*second, *first)
+        };
+        if !enemy_query.contains(target) {
+            continue;
+        }
+        let Ok((mut projectile, transform)) = projectile_query.get_mut(projectile_entity) else {
+            continue;
+        };
+        // Already spent earlier this frame, or passing through the same enemy again
+        if projectile.hits_left == 0 || projectile.hit.contains(&target) {
+            continue;
+        }
+
+        damage_events.send(DamageEvent {
+            source: projectile.owner,
+            target,
+            amount: projectile.damage,
+            damage_type: projectile.damage_type,
+            source_position: transform.translation.truncate(),
+        });
+        projectile.hit.push(target);
+        projectile.hits_left -= 1;
+        if projectile.hits_left == 0 {
+            commands.entity(projectile_entity).despawn_recursive();
+        }
+    }
+}
@@ -1,16 +1,47 @@
-use bevy::prelude::States;
+use bevy::{
+    app::{App, Plugin},
+    prelude::{AppExtStates, NextState, OnEnter, ResMut, StateSet, States, SubStates},
+};
+
+/// Sets up the app and game states, and the restart of a run that both the
+/// pause overlay and game over lead to.
+pub struct StatePlugin;
+
+impl Plugin for StatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_sub_state::<GameState>()
+            .enable_state_scoped_entities::<AppState>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_systems(OnEnter(AppState::Restarting), restart_run);
+    }
+}
 
 #[derive(Default, Debug, States, Hash, Eq, PartialEq, Clone)]
 pub enum AppState {
     #[default]
     Menu,
     InGame,
+    /// Passed through for a frame to leave the current run and start a new one
+    Restarting,
 }
 
-#[derive(Default, Debug, States, Hash, Eq, PartialEq, Clone)]
+/// State of the current run. It only exists in `AppState::InGame` and starts
+/// over as `Ongoing` each time a run begins.
+#[derive(Default, Debug, SubStates, Hash, Eq, PartialEq, Clone)]
+#[source(AppState = AppState::InGame)]
 pub enum GameState {
     #[default]
-    Paused,
     Ongoing,
+    Paused,
+    /// Play is frozen while the player picks an upgrade
+    LevelUp,
     GameOver
 }
+
+/// Leaves the run that was just torn down for a fresh one in the same world.
+/// `AppState::InGame` can't be re-entered from itself, so restarting passes
+/// through `AppState::Restarting` for a frame.
+fn restart_run(mut next_app_state: ResMut<NextState<AppState>>) {
+    next_app_state.set(AppState::InGame);
+}